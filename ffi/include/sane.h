@@ -0,0 +1,166 @@
+#ifndef SANE_H
+#define SANE_H
+
+/* Generated with cbindgen from src/lib.rs, do not edit by hand */
+
+#include <stddef.h>
+#include <stdint.h>
+
+#define SANE_F32 0
+
+#define SANE_I32 1
+
+#define SANE_U32 2
+
+#define SANE_F64 3
+
+#define SANE_I64 4
+
+#define SANE_U64 5
+
+#define SANE_I8 6
+
+#define SANE_U8 7
+
+#define SANE_I16 128
+
+#define SANE_U16 129
+
+#define SANE_F16 130
+
+#define SANE_BF16 131
+
+#define SANE_C64 132
+
+#define SANE_C128 133
+
+#define SANE_BOOL 134
+
+// The outcome of a fallible function
+typedef enum SaneStatus {
+  // The operation succeeded
+  SANE_STATUS_OK = 0,
+  // There are no more arrays to read
+  SANE_STATUS_END_OF_FILE,
+  // The file could not be read or written
+  SANE_STATUS_IO_ERROR,
+  // The file does not contain a valid SANE array
+  SANE_STATUS_INVALID_DATA,
+  // The data type code is unknown
+  SANE_STATUS_UNSUPPORTED_DATA_TYPE,
+  // A pointer is null or misaligned, or the arguments are inconsistent
+  SANE_STATUS_INVALID_ARGUMENT,
+  // The reader has not read an array yet
+  SANE_STATUS_NO_ARRAY,
+  // The destination buffer is too small
+  SANE_STATUS_BUFFER_TOO_SMALL,
+} SaneStatus;
+
+// Reads the arrays of a SANE file one at a time
+typedef struct SaneReader SaneReader;
+
+// Writes arrays to a SANE file
+typedef struct SaneWriter SaneWriter;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+// Number of bytes of a single element of the data type with the given code, or 0 if the code is
+// unknown
+size_t sane_data_type_size(uint8_t data_type);
+
+// Open the SANE file at `path` for reading, returning null if it cannot be opened
+//
+// # Safety
+//
+// `path` must be null or a valid NUL-terminated string.
+struct SaneReader *sane_reader_open(const char *path);
+
+// Close a reader and release the array it holds
+//
+// # Safety
+//
+// `reader` must be null or a pointer returned by `sane_reader_open` that has not been freed.
+void sane_reader_free(struct SaneReader *reader);
+
+// Read the next array of the file, replacing the previous one
+//
+// Returns `SANE_STATUS_END_OF_FILE` once all arrays have been read.
+//
+// # Safety
+//
+// `reader` must be null or a valid reader.
+enum SaneStatus sane_reader_next(struct SaneReader *reader);
+
+// Number of dimensions of the current array, or 0 if there is none
+//
+// # Safety
+//
+// `reader` must be null or a valid reader.
+size_t sane_reader_ndim(const struct SaneReader *reader);
+
+// Copy the shape of the current array into `shape`, which has room for `len` dimensions
+//
+// # Safety
+//
+// `reader` must be null or a valid reader, and `shape` must be valid for writing `len` values.
+enum SaneStatus sane_reader_shape(const struct SaneReader *reader, size_t *shape, size_t len);
+
+// Store the data type code of the current array in `data_type`
+//
+// # Safety
+//
+// `reader` must be null or a valid reader, and `data_type` must be null or valid for writing.
+enum SaneStatus sane_reader_data_type(const struct SaneReader *reader, uint8_t *data_type);
+
+// Number of bytes of the elements of the current array, or 0 if there is none
+//
+// # Safety
+//
+// `reader` must be null or a valid reader.
+size_t sane_reader_data_length(const struct SaneReader *reader);
+
+// Copy the elements of the current array in row-major order into `buffer`, which is `len`
+// bytes long
+//
+// # Safety
+//
+// `reader` must be null or a valid reader, and `buffer` must be valid for writing `len` bytes.
+enum SaneStatus sane_reader_copy_data(const struct SaneReader *reader, void *buffer, size_t len);
+
+// Create or truncate the file at `path` for writing arrays, returning null if it cannot be
+// created
+//
+// # Safety
+//
+// `path` must be null or a valid NUL-terminated string.
+struct SaneWriter *sane_writer_create(const char *path);
+
+// Write `data` as the elements of an array of the given data type and row-major shape
+//
+// # Safety
+//
+// `writer` must be null or a valid writer, `shape` must be valid for reading `ndim` values and
+// `data` must point to as many properly initialized elements of the data type as the shape
+// describes.
+enum SaneStatus sane_writer_write(struct SaneWriter *writer,
+                                  uint8_t data_type,
+                                  const size_t *shape,
+                                  size_t ndim,
+                                  const void *data);
+
+// Flush the written arrays to the file and close the writer
+//
+// The writer is freed even if flushing fails.
+//
+// # Safety
+//
+// `writer` must be null or a pointer returned by `sane_writer_create` that has not been closed.
+enum SaneStatus sane_writer_close(struct SaneWriter *writer);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* SANE_H */
@@ -0,0 +1,412 @@
+//! C interface for reading and writing SANE-encoded arrays
+//!
+//! The header `include/sane.h` is generated from this file with
+//! `cbindgen --config cbindgen.toml --output include/sane.h`.
+//!
+//! Arrays are read one at a time with a [`SaneReader`], which holds the most recently read array
+//! until the next one is read. Element data crosses the interface in native byte order, with
+//! shapes in row-major order.
+use std::ffi::{c_char, c_void, CStr};
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Write};
+use std::ptr::NonNull;
+
+use ndarray::{ArrayViewD, IxDyn};
+use sane_array::read::ParseError;
+use sane_array::write::WriteError;
+use sane_array::{read_sane_dyn, sane_dispatch, write_sane, DataType, Sane, SaneElement};
+
+pub const SANE_F32: u8 = 0;
+pub const SANE_I32: u8 = 1;
+pub const SANE_U32: u8 = 2;
+pub const SANE_F64: u8 = 3;
+pub const SANE_I64: u8 = 4;
+pub const SANE_U64: u8 = 5;
+pub const SANE_I8: u8 = 6;
+pub const SANE_U8: u8 = 7;
+pub const SANE_I16: u8 = 128;
+pub const SANE_U16: u8 = 129;
+pub const SANE_F16: u8 = 130;
+pub const SANE_BF16: u8 = 131;
+pub const SANE_C64: u8 = 132;
+pub const SANE_C128: u8 = 133;
+pub const SANE_BOOL: u8 = 134;
+
+/// The outcome of a fallible function
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SaneStatus {
+    /// The operation succeeded
+    Ok = 0,
+    /// There are no more arrays to read
+    EndOfFile,
+    /// The file could not be read or written
+    IoError,
+    /// The file does not contain a valid SANE array
+    InvalidData,
+    /// The data type code is unknown
+    UnsupportedDataType,
+    /// A pointer is null or misaligned, or the arguments are inconsistent
+    InvalidArgument,
+    /// The reader has not read an array yet
+    NoArray,
+    /// The destination buffer is too small
+    BufferTooSmall,
+}
+
+impl From<ParseError> for SaneStatus {
+    fn from(err: ParseError) -> Self {
+        match err {
+            ParseError::EOF => SaneStatus::EndOfFile,
+            ParseError::ReadError(_) => SaneStatus::IoError,
+            ParseError::InvalidDataType(_) | ParseError::UnsupportedDataType(_) => SaneStatus::UnsupportedDataType,
+            _ => SaneStatus::InvalidData,
+        }
+    }
+}
+
+impl From<WriteError> for SaneStatus {
+    fn from(err: WriteError) -> Self {
+        match err {
+            WriteError::Failed(_) => SaneStatus::IoError,
+            _ => SaneStatus::InvalidArgument,
+        }
+    }
+}
+
+/// Reads the arrays of a SANE file one at a time
+pub struct SaneReader {
+    reader: BufReader<File>,
+    array: Option<Sane>,
+}
+
+/// Writes arrays to a SANE file
+pub struct SaneWriter {
+    writer: BufWriter<File>,
+}
+
+/// The elements of an array read from a file, which is always in standard layout
+fn element_bytes(sane: &Sane) -> &[u8] {
+    sane_dispatch!(Sane: sane, array => bytemuck::cast_slice(array.as_slice().expect("array in standard layout")))
+}
+
+/// # Safety
+///
+/// `path` must be null or a valid NUL-terminated string.
+unsafe fn path_str<'a>(path: *const c_char) -> Option<&'a str> {
+    if path.is_null() {
+        return None;
+    }
+    CStr::from_ptr(path).to_str().ok()
+}
+
+/// Number of bytes of a single element of the data type with the given code, or 0 if the code is
+/// unknown
+#[no_mangle]
+pub extern "C" fn sane_data_type_size(data_type: u8) -> usize {
+    DataType::try_from(data_type).map_or(0, |data_type| data_type.size_in_bytes())
+}
+
+/// Open the SANE file at `path` for reading, returning null if it cannot be opened
+///
+/// # Safety
+///
+/// `path` must be null or a valid NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn sane_reader_open(path: *const c_char) -> *mut SaneReader {
+    let Some(path) = path_str(path) else {
+        return std::ptr::null_mut();
+    };
+    match File::open(path) {
+        Ok(file) => Box::into_raw(Box::new(SaneReader { reader: BufReader::new(file), array: None })),
+        Err(_) => std::ptr::null_mut(),
+    }
+}
+
+/// Close a reader and release the array it holds
+///
+/// # Safety
+///
+/// `reader` must be null or a pointer returned by `sane_reader_open` that has not been freed.
+#[no_mangle]
+pub unsafe extern "C" fn sane_reader_free(reader: *mut SaneReader) {
+    if !reader.is_null() {
+        drop(Box::from_raw(reader));
+    }
+}
+
+/// Read the next array of the file, replacing the previous one
+///
+/// Returns `SANE_STATUS_END_OF_FILE` once all arrays have been read.
+///
+/// # Safety
+///
+/// `reader` must be null or a valid reader.
+#[no_mangle]
+pub unsafe extern "C" fn sane_reader_next(reader: *mut SaneReader) -> SaneStatus {
+    let Some(reader) = reader.as_mut() else {
+        return SaneStatus::InvalidArgument;
+    };
+    reader.array = None;
+    match read_sane_dyn(&mut reader.reader) {
+        Ok(array) => {
+            reader.array = Some(array);
+            SaneStatus::Ok
+        }
+        Err(err) => err.into(),
+    }
+}
+
+/// Number of dimensions of the current array, or 0 if there is none
+///
+/// # Safety
+///
+/// `reader` must be null or a valid reader.
+#[no_mangle]
+pub unsafe extern "C" fn sane_reader_ndim(reader: *const SaneReader) -> usize {
+    reader.as_ref().and_then(|reader| reader.array.as_ref()).map_or(0, |array| array.ndim())
+}
+
+/// Copy the shape of the current array into `shape`, which has room for `len` dimensions
+///
+/// # Safety
+///
+/// `reader` must be null or a valid reader, and `shape` must be valid for writing `len` values.
+#[no_mangle]
+pub unsafe extern "C" fn sane_reader_shape(reader: *const SaneReader, shape: *mut usize, len: usize) -> SaneStatus {
+    let Some(reader) = reader.as_ref() else {
+        return SaneStatus::InvalidArgument;
+    };
+    let Some(array) = &reader.array else {
+        return SaneStatus::NoArray;
+    };
+    if len < array.ndim() {
+        return SaneStatus::BufferTooSmall;
+    }
+    if array.ndim() > 0 {
+        if shape.is_null() {
+            return SaneStatus::InvalidArgument;
+        }
+        std::slice::from_raw_parts_mut(shape, array.ndim()).copy_from_slice(array.shape());
+    }
+    SaneStatus::Ok
+}
+
+/// Store the data type code of the current array in `data_type`
+///
+/// # Safety
+///
+/// `reader` must be null or a valid reader, and `data_type` must be null or valid for writing.
+#[no_mangle]
+pub unsafe extern "C" fn sane_reader_data_type(reader: *const SaneReader, data_type: *mut u8) -> SaneStatus {
+    let (Some(reader), Some(data_type)) = (reader.as_ref(), data_type.as_mut()) else {
+        return SaneStatus::InvalidArgument;
+    };
+    match &reader.array {
+        Some(array) => {
+            *data_type = array.data_type().into();
+            SaneStatus::Ok
+        }
+        None => SaneStatus::NoArray,
+    }
+}
+
+/// Number of bytes of the elements of the current array, or 0 if there is none
+///
+/// # Safety
+///
+/// `reader` must be null or a valid reader.
+#[no_mangle]
+pub unsafe extern "C" fn sane_reader_data_length(reader: *const SaneReader) -> usize {
+    reader.as_ref().and_then(|reader| reader.array.as_ref()).map_or(0, |array| array.byte_len())
+}
+
+/// Copy the elements of the current array in row-major order into `buffer`, which is `len`
+/// bytes long
+///
+/// # Safety
+///
+/// `reader` must be null or a valid reader, and `buffer` must be valid for writing `len` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn sane_reader_copy_data(reader: *const SaneReader, buffer: *mut c_void, len: usize) -> SaneStatus {
+    let Some(reader) = reader.as_ref() else {
+        return SaneStatus::InvalidArgument;
+    };
+    let Some(array) = &reader.array else {
+        return SaneStatus::NoArray;
+    };
+    let bytes = element_bytes(array);
+    if len < bytes.len() {
+        return SaneStatus::BufferTooSmall;
+    }
+    if !bytes.is_empty() {
+        if buffer.is_null() {
+            return SaneStatus::InvalidArgument;
+        }
+        std::slice::from_raw_parts_mut(buffer.cast::<u8>(), bytes.len()).copy_from_slice(bytes);
+    }
+    SaneStatus::Ok
+}
+
+/// Create or truncate the file at `path` for writing arrays, returning null if it cannot be
+/// created
+///
+/// # Safety
+///
+/// `path` must be null or a valid NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn sane_writer_create(path: *const c_char) -> *mut SaneWriter {
+    let Some(path) = path_str(path) else {
+        return std::ptr::null_mut();
+    };
+    match File::create(path) {
+        Ok(file) => Box::into_raw(Box::new(SaneWriter { writer: BufWriter::new(file) })),
+        Err(_) => std::ptr::null_mut(),
+    }
+}
+
+/// Write `data` as the elements of an array of the given data type and row-major shape
+///
+/// # Safety
+///
+/// `writer` must be null or a valid writer, `shape` must be valid for reading `ndim` values and
+/// `data` must point to as many properly initialized elements of the data type as the shape
+/// describes. `SANE_BOOL` elements are bytes, and any byte other than 0 or 1 is rejected.
+#[no_mangle]
+pub unsafe extern "C" fn sane_writer_write(
+    writer: *mut SaneWriter,
+    data_type: u8,
+    shape: *const usize,
+    ndim: usize,
+    data: *const c_void,
+) -> SaneStatus {
+    let Some(writer) = writer.as_mut() else {
+        return SaneStatus::InvalidArgument;
+    };
+    let Ok(data_type) = DataType::try_from(data_type) else {
+        return SaneStatus::UnsupportedDataType;
+    };
+    let shape = match ndim {
+        0 => &[][..],
+        _ if shape.is_null() => return SaneStatus::InvalidArgument,
+        _ => std::slice::from_raw_parts(shape, ndim),
+    };
+    let Some(len) = shape.iter().try_fold(1usize, |len, &dim| len.checked_mul(dim)) else {
+        return SaneStatus::InvalidArgument;
+    };
+    match len.checked_mul(data_type.size_in_bytes()) {
+        Some(byte_len) if byte_len <= isize::MAX as usize => {}
+        _ => return SaneStatus::InvalidArgument,
+    }
+    if data_type == DataType::Bool && len > 0 {
+        if data.is_null() {
+            return SaneStatus::InvalidArgument;
+        }
+        if std::slice::from_raw_parts(data.cast::<u8>(), len).iter().any(|&byte| byte > 1) {
+            return SaneStatus::InvalidArgument;
+        }
+    }
+    sane_dispatch!(
+        DataType: data_type,
+        A => write_elements::<A>(&mut writer.writer, shape, len, data),
+        _ => SaneStatus::UnsupportedDataType
+    )
+}
+
+/// # Safety
+///
+/// `data` must point to `len` initialized elements of type `A`.
+unsafe fn write_elements<A: SaneElement>(writer: &mut BufWriter<File>, shape: &[usize], len: usize, data: *const c_void) -> SaneStatus {
+    let data = if len == 0 {
+        NonNull::<A>::dangling().as_ptr().cast_const()
+    } else if data.is_null() || data as usize % std::mem::align_of::<A>() != 0 {
+        return SaneStatus::InvalidArgument;
+    } else {
+        data.cast::<A>()
+    };
+    let view = ArrayViewD::from_shape_ptr(IxDyn(shape), data);
+    match write_sane(writer, &view) {
+        Ok(()) => SaneStatus::Ok,
+        Err(err) => err.into(),
+    }
+}
+
+/// Flush the written arrays to the file and close the writer
+///
+/// The writer is freed even if flushing fails.
+///
+/// # Safety
+///
+/// `writer` must be null or a pointer returned by `sane_writer_create` that has not been closed.
+#[no_mangle]
+pub unsafe extern "C" fn sane_writer_close(writer: *mut SaneWriter) -> SaneStatus {
+    if writer.is_null() {
+        return SaneStatus::InvalidArgument;
+    }
+    let mut writer = Box::from_raw(writer);
+    match writer.writer.flush() {
+        Ok(()) => SaneStatus::Ok,
+        Err(_) => SaneStatus::IoError,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::CString;
+
+    use sane_array::DataType;
+
+    use super::*;
+
+    #[test]
+    fn data_type_codes() {
+        let codes = [
+            SANE_F32, SANE_I32, SANE_U32, SANE_F64, SANE_I64, SANE_U64, SANE_I8, SANE_U8, SANE_I16, SANE_U16,
+            SANE_F16, SANE_BF16, SANE_C64, SANE_C128, SANE_BOOL,
+        ];
+        for (code, data_type) in codes.into_iter().zip(DataType::ALL) {
+            assert_eq!(code, u8::from(data_type));
+        }
+    }
+
+    #[test]
+    fn roundtrip() {
+        let path = std::env::temp_dir().join(format!("sane-ffi-test-{}.sane", std::process::id()));
+        let path = CString::new(path.to_str().unwrap()).unwrap();
+        let values = [1i16, -2, 3, -4, 5, -6];
+        unsafe {
+            let writer = sane_writer_create(path.as_ptr());
+            assert!(!writer.is_null());
+            let shape = [2usize, 3];
+            assert_eq!(sane_writer_write(writer, SANE_I16, shape.as_ptr(), 2, values.as_ptr().cast()), SaneStatus::Ok);
+            assert_eq!(sane_writer_write(writer, 200, shape.as_ptr(), 2, values.as_ptr().cast()), SaneStatus::UnsupportedDataType);
+            let misaligned = values.as_ptr().cast::<u8>().add(1).cast();
+            assert_eq!(sane_writer_write(writer, SANE_I16, shape.as_ptr(), 1, misaligned), SaneStatus::InvalidArgument);
+            let huge = [usize::MAX / 2 + 1];
+            assert_eq!(sane_writer_write(writer, SANE_I16, huge.as_ptr(), 1, values.as_ptr().cast()), SaneStatus::InvalidArgument);
+            let bools = [1u8, 2];
+            assert_eq!(sane_writer_write(writer, SANE_BOOL, [2usize].as_ptr(), 1, bools.as_ptr().cast()), SaneStatus::InvalidArgument);
+            assert_eq!(sane_writer_close(writer), SaneStatus::Ok);
+
+            let reader = sane_reader_open(path.as_ptr());
+            assert!(!reader.is_null());
+            assert_eq!(sane_reader_data_length(reader), 0);
+            assert_eq!(sane_reader_next(reader), SaneStatus::Ok);
+            let mut shape = [0usize; 2];
+            assert_eq!(sane_reader_shape(reader, shape.as_mut_ptr(), 1), SaneStatus::BufferTooSmall);
+            assert_eq!(sane_reader_shape(reader, shape.as_mut_ptr(), 2), SaneStatus::Ok);
+            assert_eq!(shape, [2, 3]);
+            let mut data_type = 0;
+            assert_eq!(sane_reader_data_type(reader, &mut data_type), SaneStatus::Ok);
+            assert_eq!(data_type, SANE_I16);
+            assert_eq!(sane_reader_data_length(reader), 12);
+            let mut read = [0i16; 6];
+            assert_eq!(sane_reader_copy_data(reader, read.as_mut_ptr().cast(), 12), SaneStatus::Ok);
+            assert_eq!(read, values);
+            assert_eq!(sane_reader_next(reader), SaneStatus::EndOfFile);
+            assert_eq!(sane_reader_ndim(reader), 0);
+            sane_reader_free(reader);
+        }
+        std::fs::remove_file(path.to_str().unwrap()).unwrap();
+    }
+}
@@ -0,0 +1,35 @@
+//! Compile `tests/test.c` against the static library and run it
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+#[test]
+fn c_program() {
+    let manifest_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+    // Integration tests are built into `target/<profile>/deps`, next to the static library
+    let exe = std::env::current_exe().unwrap();
+    let library = exe.with_file_name("libsane_ffi.a");
+    let out_dir = std::env::temp_dir().join(format!("sane-ffi-c-{}", std::process::id()));
+    std::fs::create_dir_all(&out_dir).unwrap();
+    let program = out_dir.join("test");
+
+    let cc = std::env::var("CC").unwrap_or_else(|_| "cc".to_string());
+    let status = Command::new(cc)
+        .arg("-std=c99")
+        .arg("-Wall")
+        .arg("-Werror")
+        .arg("-I")
+        .arg(manifest_dir.join("include"))
+        .arg(manifest_dir.join("tests/test.c"))
+        .arg(&library)
+        .args(["-lpthread", "-ldl", "-lm"])
+        .arg("-o")
+        .arg(&program)
+        .status()
+        .expect("failed to run the C compiler");
+    assert!(status.success(), "failed to compile tests/test.c");
+
+    let data: PathBuf = out_dir.join("arrays.sane");
+    let status = Command::new(&program).arg(&data).status().unwrap();
+    std::fs::remove_dir_all(&out_dir).unwrap();
+    assert!(status.success(), "tests/test.c failed");
+}
@@ -0,0 +1,71 @@
+/* Writes arrays through the C interface, reads them back and checks the result */
+#include <stdbool.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+
+#include "sane.h"
+
+#define CHECK(condition)                                                  \
+    do {                                                                  \
+        if (!(condition)) {                                               \
+            fprintf(stderr, "%s:%d: check failed: %s\n", __FILE__,        \
+                    __LINE__, #condition);                                \
+            return 1;                                                     \
+        }                                                                 \
+    } while (0)
+
+int main(int argc, char **argv) {
+    if (argc != 2) {
+        fprintf(stderr, "usage: %s <path>\n", argv[0]);
+        return 2;
+    }
+    const char *path = argv[1];
+
+    float matrix[2][3] = {{1.0f, 2.0f, 3.0f}, {4.0f, 5.0f, 6.0f}};
+    size_t matrix_shape[2] = {2, 3};
+    bool flags[4] = {true, false, false, true};
+    size_t flags_shape[1] = {4};
+
+    SaneWriter *writer = sane_writer_create(path);
+    CHECK(writer != NULL);
+    CHECK(sane_writer_write(writer, SANE_F32, matrix_shape, 2, matrix) == SANE_STATUS_OK);
+    CHECK(sane_writer_write(writer, SANE_BOOL, flags_shape, 1, flags) == SANE_STATUS_OK);
+    CHECK(sane_writer_write(writer, 255, flags_shape, 1, flags) == SANE_STATUS_UNSUPPORTED_DATA_TYPE);
+    CHECK(sane_writer_close(writer) == SANE_STATUS_OK);
+
+    SaneReader *reader = sane_reader_open(path);
+    CHECK(reader != NULL);
+    CHECK(sane_reader_copy_data(reader, NULL, 0) == SANE_STATUS_NO_ARRAY);
+
+    CHECK(sane_reader_next(reader) == SANE_STATUS_OK);
+    size_t ndim = sane_reader_ndim(reader);
+    CHECK(ndim == 2);
+    size_t shape[2];
+    CHECK(sane_reader_shape(reader, shape, ndim) == SANE_STATUS_OK);
+    CHECK(shape[0] == 2 && shape[1] == 3);
+    uint8_t data_type;
+    CHECK(sane_reader_data_type(reader, &data_type) == SANE_STATUS_OK);
+    CHECK(data_type == SANE_F32);
+    size_t length = sane_reader_data_length(reader);
+    CHECK(length == sizeof(matrix));
+    CHECK(length == shape[0] * shape[1] * sane_data_type_size(data_type));
+    float *values = malloc(length);
+    CHECK(sane_reader_copy_data(reader, values, length - 1) == SANE_STATUS_BUFFER_TOO_SMALL);
+    CHECK(sane_reader_copy_data(reader, values, length) == SANE_STATUS_OK);
+    CHECK(memcmp(values, matrix, length) == 0);
+    free(values);
+
+    CHECK(sane_reader_next(reader) == SANE_STATUS_OK);
+    CHECK(sane_reader_data_type(reader, &data_type) == SANE_STATUS_OK);
+    CHECK(data_type == SANE_BOOL);
+    bool read_flags[4];
+    CHECK(sane_reader_copy_data(reader, read_flags, sizeof(read_flags)) == SANE_STATUS_OK);
+    CHECK(memcmp(read_flags, flags, sizeof(flags)) == 0);
+
+    CHECK(sane_reader_next(reader) == SANE_STATUS_END_OF_FILE);
+    sane_reader_free(reader);
+
+    CHECK(sane_reader_open("/nonexistent/array.sane") == NULL);
+    return 0;
+}
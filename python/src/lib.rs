@@ -0,0 +1,127 @@
+//! Python bindings for reading and writing SANE-encoded arrays as NumPy arrays
+//!
+//! The module is built with [maturin](https://www.maturin.rs) and imported as `sane`.
+use std::fs::File;
+use std::io::BufReader;
+use std::path::PathBuf;
+
+use numpy::{PyArray, PyArrayDyn, PyArrayMethods, PyUntypedArray, PyUntypedArrayMethods};
+use pyo3::create_exception;
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use sane_array::read::ParseError;
+use sane_array::write::WriteError;
+use sane_array::{
+    read_sane_arrays_dyn, read_sane_dyn, read_sane_file_dyn, sane_dispatch, write_sane_arrays_file_dyn, DataType,
+    Sane,
+};
+
+create_exception!(sane, SaneError, PyValueError, "Raised for data that is not a valid SANE array");
+
+fn parse_error(err: ParseError) -> PyErr {
+    match err {
+        ParseError::ReadError(err) => err.into(),
+        err => SaneError::new_err(err.to_string()),
+    }
+}
+
+fn write_error(err: WriteError) -> PyErr {
+    match err {
+        WriteError::Failed(err) => err.into(),
+        err => SaneError::new_err(err.to_string()),
+    }
+}
+
+/// Move the elements of an array into a new NumPy array
+fn to_numpy(py: Python<'_>, sane: Sane) -> Bound<'_, PyAny> {
+    sane_dispatch!(Sane: sane, array => PyArray::from_owned_array(py, array).into_any())
+}
+
+/// Copy the elements of a NumPy array, or anything `numpy.asarray` accepts, into an array
+fn from_numpy(array: &Bound<'_, PyAny>) -> PyResult<Sane> {
+    let array = array.py().import("numpy")?.call_method1("asarray", (array,))?;
+    let dtype = array.cast::<PyUntypedArray>()?.dtype().str()?.to_string();
+    let data_type: DataType = dtype.parse()
+        .map_err(|_| SaneError::new_err(format!("unsupported dtype {}", dtype)))?;
+    sane_dispatch!(
+        DataType: data_type,
+        A => {
+            let typed = array.cast::<PyArrayDyn<A>>()?;
+            Ok(Sane::from(typed.readonly().as_array().to_owned()))
+        },
+        _ => Err(SaneError::new_err(format!("unsupported dtype {}", dtype)))
+    )
+}
+
+/// Read the first array of a SANE file
+#[pyfunction]
+fn load(py: Python<'_>, path: PathBuf) -> PyResult<Bound<'_, PyAny>> {
+    let sane = py.detach(|| read_sane_file_dyn(path)).map_err(parse_error)?;
+    Ok(to_numpy(py, sane))
+}
+
+/// Read all arrays of a SANE file
+#[pyfunction]
+fn load_all(py: Python<'_>, path: PathBuf) -> PyResult<Vec<Bound<'_, PyAny>>> {
+    let arrays = py.detach(|| {
+        let file = File::open(path).map_err(ParseError::ReadError)?;
+        read_sane_arrays_dyn(&mut BufReader::new(file))
+    }).map_err(parse_error)?;
+    Ok(arrays.into_iter().map(|sane| to_numpy(py, sane)).collect())
+}
+
+/// Write an array to a SANE file, replacing the file atomically
+#[pyfunction]
+fn save(py: Python<'_>, path: PathBuf, array: &Bound<'_, PyAny>) -> PyResult<()> {
+    let sane = from_numpy(array)?;
+    py.detach(|| write_sane_arrays_file_dyn(path, [&sane])).map_err(write_error)
+}
+
+/// Write a sequence of arrays to a SANE file, replacing the file atomically
+#[pyfunction]
+fn save_all(py: Python<'_>, path: PathBuf, arrays: Vec<Bound<'_, PyAny>>) -> PyResult<()> {
+    let arrays = arrays.iter().map(from_numpy).collect::<PyResult<Vec<_>>>()?;
+    py.detach(|| write_sane_arrays_file_dyn(path, &arrays)).map_err(write_error)
+}
+
+/// Iterator over the arrays of a SANE file, reading one array at a time
+#[pyclass]
+struct SaneReader {
+    reader: BufReader<File>,
+}
+
+#[pymethods]
+impl SaneReader {
+    fn __iter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    fn __next__<'py>(&mut self, py: Python<'py>) -> PyResult<Option<Bound<'py, PyAny>>> {
+        match py.detach(|| read_sane_dyn(&mut self.reader)) {
+            Ok(sane) => Ok(Some(to_numpy(py, sane))),
+            Err(ParseError::EOF) => Ok(None),
+            Err(err) => Err(parse_error(err)),
+        }
+    }
+}
+
+/// Lazily read the arrays of a SANE file one at a time
+#[pyfunction]
+fn load_iter(path: PathBuf) -> PyResult<SaneReader> {
+    let file = File::open(path)?;
+    Ok(SaneReader { reader: BufReader::new(file) })
+}
+
+#[pymodule]
+fn sane(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    // Fail on import rather than on first use if NumPy is missing
+    m.py().import("numpy")?;
+    m.add("SaneError", m.py().get_type::<SaneError>())?;
+    m.add_class::<SaneReader>()?;
+    m.add_function(wrap_pyfunction!(load, m)?)?;
+    m.add_function(wrap_pyfunction!(load_all, m)?)?;
+    m.add_function(wrap_pyfunction!(save, m)?)?;
+    m.add_function(wrap_pyfunction!(save_all, m)?)?;
+    m.add_function(wrap_pyfunction!(load_iter, m)?)?;
+    Ok(())
+}
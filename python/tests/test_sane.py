@@ -0,0 +1,45 @@
+import numpy as np
+import pytest
+
+import sane
+
+
+def test_roundtrip(tmp_path):
+    path = tmp_path / "array.sane"
+    array = np.arange(24, dtype=np.int16).reshape(2, 3, 4)
+    sane.save(path, array)
+    loaded = sane.load(path)
+    assert loaded.dtype == np.int16
+    np.testing.assert_array_equal(loaded, array)
+
+
+def test_roundtrip_all(tmp_path):
+    path = tmp_path / "arrays.sane"
+    arrays = [
+        np.array([[1.5, 2.5]], dtype=np.float32),
+        np.array([True, False]),
+        np.array([1 + 2j], dtype=np.complex128),
+        np.asfortranarray(np.arange(6, dtype=np.uint64).reshape(2, 3)),
+    ]
+    sane.save_all(path, arrays)
+    for loaded, array in zip(sane.load_all(path), arrays):
+        assert loaded.dtype == array.dtype
+        np.testing.assert_array_equal(loaded, array)
+    assert [a.shape for a in sane.load_iter(path)] == [a.shape for a in arrays]
+
+
+def test_lists_are_converted(tmp_path):
+    path = tmp_path / "list.sane"
+    sane.save(path, [[1, 2], [3, 4]])
+    np.testing.assert_array_equal(sane.load(path), np.array([[1, 2], [3, 4]]))
+
+
+def test_errors(tmp_path):
+    with pytest.raises(FileNotFoundError):
+        sane.load(tmp_path / "missing.sane")
+    path = tmp_path / "garbage.sane"
+    path.write_bytes(b"not a sane file")
+    with pytest.raises(sane.SaneError):
+        sane.load(path)
+    with pytest.raises(sane.SaneError):
+        sane.save(path, np.array(["strings"]))
@@ -0,0 +1,101 @@
+//! [`quickcheck::Arbitrary`] implementations for generating random SANE arrays
+use ndarray::{Array, ArrayD, Dimension, IxDyn};
+use quickcheck::{Arbitrary, Gen};
+
+use crate::data::{DataType, Sane};
+use crate::element::SaneElement;
+
+/// Maximum number of dimensions of generated arrays of dynamic rank
+pub const MAX_NDIM: usize = 4;
+
+/// Maximum length of each axis of generated arrays
+pub const MAX_AXIS_LEN: usize = 5;
+
+impl Arbitrary for DataType {
+    fn arbitrary(gen: &mut Gen) -> Self {
+        *gen.choose(&DataType::ALL).unwrap()
+    }
+}
+
+/// Element types that can be generated by quickcheck
+pub trait ArbitraryElement: SaneElement {
+    fn arbitrary_element(gen: &mut Gen) -> Self;
+}
+
+macro_rules! arbitrary_element {
+    ($($t:ty),*) => {
+        $(impl ArbitraryElement for $t {
+            fn arbitrary_element(gen: &mut Gen) -> Self {
+                <$t>::arbitrary(gen)
+            }
+        })*
+    }
+}
+
+arbitrary_element!(f32, i32, u32, f64, i64, u64, i8, u8, i16, u16, bool);
+
+#[cfg(feature = "half")]
+impl ArbitraryElement for half::f16 {
+    fn arbitrary_element(gen: &mut Gen) -> Self {
+        half::f16::from_f32(f32::arbitrary(gen))
+    }
+}
+
+#[cfg(feature = "half")]
+impl ArbitraryElement for half::bf16 {
+    fn arbitrary_element(gen: &mut Gen) -> Self {
+        half::bf16::from_f32(f32::arbitrary(gen))
+    }
+}
+
+#[cfg(feature = "num-complex")]
+impl ArbitraryElement for num_complex::Complex<f32> {
+    fn arbitrary_element(gen: &mut Gen) -> Self {
+        num_complex::Complex::new(f32::arbitrary(gen), f32::arbitrary(gen))
+    }
+}
+
+#[cfg(feature = "num-complex")]
+impl ArbitraryElement for num_complex::Complex<f64> {
+    fn arbitrary_element(gen: &mut Gen) -> Self {
+        num_complex::Complex::new(f64::arbitrary(gen), f64::arbitrary(gen))
+    }
+}
+
+/// Generate an array with `ndim` dimensions, each at most [`MAX_AXIS_LEN`] long
+fn arbitrary_array<A: ArbitraryElement>(gen: &mut Gen, ndim: usize) -> ArrayD<A> {
+    let shape: Vec<usize> = (0..ndim).map(|_| usize::arbitrary(gen) % (MAX_AXIS_LEN + 1)).collect();
+    Array::from_shape_fn(IxDyn(&shape), |_| A::arbitrary_element(gen))
+}
+
+/// Arrays of data types that are enabled in this build, with at most [`MAX_NDIM`] dimensions
+impl Arbitrary for Sane {
+    fn arbitrary(gen: &mut Gen) -> Self {
+        loop {
+            let data_type = DataType::arbitrary(gen);
+            let ndim = usize::arbitrary(gen) % (MAX_NDIM + 1);
+            let sane = crate::sane_dispatch!(
+                DataType: data_type,
+                A => Some(Sane::from(arbitrary_array::<A>(gen, ndim))),
+                _ => None
+            );
+            if let Some(sane) = sane {
+                return sane;
+            }
+        }
+    }
+}
+
+/// An array with a bounded random shape, for use as a quickcheck property argument
+///
+/// Arrays of dynamic rank have at most [`MAX_NDIM`] dimensions.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ArbitraryArray<A, D: Dimension>(pub Array<A, D>);
+
+impl<A: ArbitraryElement + 'static, D: Dimension + 'static> Arbitrary for ArbitraryArray<A, D> {
+    fn arbitrary(gen: &mut Gen) -> Self {
+        let ndim = D::NDIM.unwrap_or_else(|| usize::arbitrary(gen) % (MAX_NDIM + 1));
+        let array = arbitrary_array(gen, ndim).into_dimensionality().unwrap();
+        ArbitraryArray(array)
+    }
+}
@@ -0,0 +1,413 @@
+//! Conversion between [`Sane`] arrays and [Apache Arrow](https://arrow.apache.org) arrays
+//!
+//! One-dimensional arrays convert to Arrow primitive (or boolean) arrays, and arrays of higher
+//! rank to nested `FixedSizeList` arrays with one level per axis after the first. Alternatively,
+//! [`to_arrow_tensor`] converts an array to the canonical
+//! [fixed shape tensor](https://arrow.apache.org/docs/format/CanonicalExtensions.html#fixed-shape-tensor)
+//! extension type, with one tensor per index of the first axis.
+//!
+//! Element buffers are moved rather than copied where possible: always when converting an array
+//! in standard layout to Arrow, and when converting from Arrow if the buffer is not shared and
+//! was allocated for the element type. Arrow booleans are bit-packed and are always copied.
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use arrow_array::cast::downcast_array;
+use arrow_array::types::{
+    Float32Type, Float64Type, Int16Type, Int32Type, Int64Type, Int8Type, UInt16Type, UInt32Type,
+    UInt64Type, UInt8Type,
+};
+#[cfg(feature = "half")]
+use arrow_array::types::Float16Type;
+use arrow_array::{Array as _, ArrayRef, BooleanArray, FixedSizeListArray, PrimitiveArray};
+use arrow_buffer::ScalarBuffer;
+use arrow_schema::extension::{EXTENSION_TYPE_METADATA_KEY, EXTENSION_TYPE_NAME_KEY};
+use arrow_schema::{DataType as ArrowDataType, Field};
+use ndarray::{Array, ArrayD, Dimension, ShapeError};
+
+use crate::data::{DataType, Sane};
+use crate::element::SaneElement;
+
+/// The extension name of the canonical fixed shape tensor type
+pub const FIXED_SHAPE_TENSOR: &str = "arrow.fixed_shape_tensor";
+
+#[derive(Debug)]
+pub enum ArrowError {
+    UnsupportedDataType(DataType),
+    UnsupportedArrowType(ArrowDataType),
+    ZeroDimensional,
+    AxisTooLong(usize),
+    Nulls,
+    InvalidTensor(String),
+    ShapeError(ShapeError),
+    Arrow(arrow_schema::ArrowError),
+}
+
+impl std::fmt::Display for ArrowError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        use ArrowError::*;
+        match self {
+            UnsupportedDataType(data_type) => write!(f, "Data type {} has no Arrow equivalent", data_type),
+            UnsupportedArrowType(arrow_type) => write!(f, "Unsupported Arrow data type {}", arrow_type),
+            ZeroDimensional => write!(f, "Zero-dimensional arrays have no Arrow equivalent"),
+            AxisTooLong(len) => write!(f, "Axis of length {} is too long for an Arrow list", len),
+            Nulls => write!(f, "Arrow array contains null values"),
+            InvalidTensor(reason) => write!(f, "Invalid fixed shape tensor: {}", reason),
+            ShapeError(err) => write!(f, "{}", err),
+            Arrow(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for ArrowError {}
+
+/// The Arrow data type of the elements of an array with the given data type, if there is one
+pub fn arrow_data_type(data_type: DataType) -> Option<ArrowDataType> {
+    use DataType::*;
+    match data_type {
+        F32 => Some(ArrowDataType::Float32),
+        I32 => Some(ArrowDataType::Int32),
+        U32 => Some(ArrowDataType::UInt32),
+        F64 => Some(ArrowDataType::Float64),
+        I64 => Some(ArrowDataType::Int64),
+        U64 => Some(ArrowDataType::UInt64),
+        I8 => Some(ArrowDataType::Int8),
+        U8 => Some(ArrowDataType::UInt8),
+        I16 => Some(ArrowDataType::Int16),
+        U16 => Some(ArrowDataType::UInt16),
+        F16 => Some(ArrowDataType::Float16),
+        Bool => Some(ArrowDataType::Boolean),
+        BF16 | C64 | C128 => None,
+    }
+}
+
+/// The data type corresponding to an Arrow primitive or boolean data type, if there is one
+pub fn from_arrow_data_type(arrow_type: &ArrowDataType) -> Option<DataType> {
+    DataType::ALL.into_iter().find(|&data_type| arrow_data_type(data_type).as_ref() == Some(arrow_type))
+}
+
+/// Conversion of the elements of a one-dimensional array from and to an Arrow array
+trait ArrowElement: SaneElement {
+    fn into_arrow(values: Vec<Self>) -> Result<ArrayRef, ArrowError>;
+
+    fn from_arrow(values: ArrayRef) -> Result<Vec<Self>, ArrowError>;
+}
+
+macro_rules! primitive_element {
+    ($t:ty, $arrow:ty) => {
+        impl ArrowElement for $t {
+            fn into_arrow(values: Vec<Self>) -> Result<ArrayRef, ArrowError> {
+                Ok(Arc::new(PrimitiveArray::<$arrow>::new(ScalarBuffer::from(values), None)))
+            }
+
+            fn from_arrow(values: ArrayRef) -> Result<Vec<Self>, ArrowError> {
+                let primitive: PrimitiveArray<$arrow> = downcast_array(&*values);
+                // Release the original array so that the buffer can be taken over if it is not
+                // shared elsewhere
+                drop(values);
+                let (_, buffer, _) = primitive.into_parts();
+                Ok(buffer.into_inner().into_vec().unwrap_or_else(|buffer| buffer.typed_data().to_vec()))
+            }
+        }
+    }
+}
+
+primitive_element!(f32, Float32Type);
+primitive_element!(i32, Int32Type);
+primitive_element!(u32, UInt32Type);
+primitive_element!(f64, Float64Type);
+primitive_element!(i64, Int64Type);
+primitive_element!(u64, UInt64Type);
+primitive_element!(i8, Int8Type);
+primitive_element!(u8, UInt8Type);
+primitive_element!(i16, Int16Type);
+primitive_element!(u16, UInt16Type);
+#[cfg(feature = "half")]
+primitive_element!(half::f16, Float16Type);
+
+impl ArrowElement for bool {
+    fn into_arrow(values: Vec<Self>) -> Result<ArrayRef, ArrowError> {
+        Ok(Arc::new(BooleanArray::from(values)))
+    }
+
+    fn from_arrow(values: ArrayRef) -> Result<Vec<Self>, ArrowError> {
+        let booleans: BooleanArray = downcast_array(&*values);
+        Ok(booleans.values().iter().collect())
+    }
+}
+
+// Element types without an Arrow equivalent
+#[cfg(any(feature = "half", feature = "num-complex"))]
+macro_rules! unsupported_element {
+    ($t:ty) => {
+        impl ArrowElement for $t {
+            fn into_arrow(_values: Vec<Self>) -> Result<ArrayRef, ArrowError> {
+                Err(ArrowError::UnsupportedDataType(Self::DATA_TYPE))
+            }
+
+            fn from_arrow(_values: ArrayRef) -> Result<Vec<Self>, ArrowError> {
+                Err(ArrowError::UnsupportedDataType(Self::DATA_TYPE))
+            }
+        }
+    }
+}
+
+#[cfg(feature = "half")]
+unsupported_element!(half::bf16);
+#[cfg(feature = "num-complex")]
+unsupported_element!(num_complex::Complex<f32>);
+#[cfg(feature = "num-complex")]
+unsupported_element!(num_complex::Complex<f64>);
+
+/// The elements of an array in row-major order, reusing its allocation if it is in standard
+/// layout
+fn into_standard_vec<A: Clone, D: Dimension>(array: Array<A, D>) -> Vec<A> {
+    if !array.is_standard_layout() {
+        return array.iter().cloned().collect();
+    }
+    let len = array.len();
+    if len == 0 {
+        return Vec::new();
+    }
+    let first = array.as_ptr() as usize;
+    let mut values = array.into_raw_vec();
+    // The array may start anywhere in its allocation after slicing
+    let offset = (first - values.as_ptr() as usize) / std::mem::size_of::<A>();
+    values.truncate(offset + len);
+    values.drain(..offset);
+    values
+}
+
+/// Group `values` into `len` lists of `size` elements each
+fn fixed_size_list(values: ArrayRef, size: usize, len: usize) -> Result<ArrayRef, ArrowError> {
+    let list_size = i32::try_from(size).map_err(|_| ArrowError::AxisTooLong(size))?;
+    let field = Arc::new(Field::new_list_field(values.data_type().clone(), false));
+    let list = FixedSizeListArray::try_new_with_length(field, list_size, values, None, len)
+        .map_err(ArrowError::Arrow)?;
+    Ok(Arc::new(list))
+}
+
+/// The elements of all lists of a `FixedSizeList` array
+fn list_values(list: ArrayRef) -> Result<ArrayRef, ArrowError> {
+    if list.null_count() > 0 {
+        return Err(ArrowError::Nulls);
+    }
+    let list: FixedSizeListArray = downcast_array(&*list);
+    let len = list.len() * list.value_length() as usize;
+    let (_, _, values, _) = list.into_parts();
+    if values.len() == len {
+        Ok(values)
+    } else {
+        Ok(values.slice(0, len))
+    }
+}
+
+/// Convert the elements of a primitive or boolean Arrow array to an array of the given shape
+fn values_to_sane(values: ArrayRef, shape: Vec<usize>) -> Result<Sane, ArrowError> {
+    if values.null_count() > 0 {
+        return Err(ArrowError::Nulls);
+    }
+    let arrow_type = values.data_type().clone();
+    let data_type = from_arrow_data_type(&arrow_type).ok_or_else(|| ArrowError::UnsupportedArrowType(arrow_type.clone()))?;
+    crate::sane_dispatch!(
+        DataType: data_type,
+        A => {
+            let values = A::from_arrow(values)?;
+            ArrayD::from_shape_vec(shape, values).map(Sane::from).map_err(ArrowError::ShapeError)
+        },
+        _ => Err(ArrowError::UnsupportedArrowType(arrow_type))
+    )
+}
+
+/// Convert an array to a primitive Arrow array if it is one-dimensional, and to nested
+/// `FixedSizeList` arrays otherwise
+pub fn to_arrow(sane: Sane) -> Result<ArrayRef, ArrowError> {
+    let shape = sane.shape().to_vec();
+    if shape.is_empty() {
+        return Err(ArrowError::ZeroDimensional);
+    }
+    let mut array = crate::sane_dispatch!(Sane: sane, array => ArrowElement::into_arrow(into_standard_vec(array)))?;
+    for axis in (1..shape.len()).rev() {
+        array = fixed_size_list(array, shape[axis], shape[..axis].iter().product())?;
+    }
+    Ok(array)
+}
+
+/// Convert a primitive Arrow array or nested `FixedSizeList` arrays of primitives to an array
+/// with one axis per level of nesting
+pub fn from_arrow(array: ArrayRef) -> Result<Sane, ArrowError> {
+    let mut shape = vec![array.len()];
+    let mut values = array;
+    while let ArrowDataType::FixedSizeList(_, size) = values.data_type() {
+        shape.push(*size as usize);
+        values = list_values(values)?;
+    }
+    values_to_sane(values, shape)
+}
+
+/// Convert an array to a column of fixed shape tensors named `name`, one tensor per index of the
+/// first axis
+///
+/// The extension type is recorded in the metadata of the returned field.
+pub fn to_arrow_tensor(name: &str, sane: Sane) -> Result<(Field, ArrayRef), ArrowError> {
+    let shape = sane.shape().to_vec();
+    let Some((&len, tensor_shape)) = shape.split_first() else {
+        return Err(ArrowError::ZeroDimensional);
+    };
+    let values = crate::sane_dispatch!(Sane: sane, array => ArrowElement::into_arrow(into_standard_vec(array)))?;
+    let array = fixed_size_list(values, tensor_shape.iter().product(), len)?;
+    let metadata = HashMap::from([
+        (EXTENSION_TYPE_NAME_KEY.to_string(), FIXED_SHAPE_TENSOR.to_string()),
+        (EXTENSION_TYPE_METADATA_KEY.to_string(), serde_json::json!({ "shape": tensor_shape }).to_string()),
+    ]);
+    let field = Field::new(name, array.data_type().clone(), false).with_metadata(metadata);
+    Ok((field, array))
+}
+
+/// Convert a column of fixed shape tensors to an array whose first axis indexes the tensors
+///
+/// Tensors with permuted dimensions are transposed into their logical shape.
+pub fn from_arrow_tensor(field: &Field, array: ArrayRef) -> Result<Sane, ArrowError> {
+    if field.extension_type_name() != Some(FIXED_SHAPE_TENSOR) {
+        return Err(ArrowError::InvalidTensor(format!("field {} is not a fixed shape tensor", field.name())));
+    }
+    let metadata = field.extension_type_metadata()
+        .ok_or_else(|| ArrowError::InvalidTensor("missing metadata".to_string()))?;
+    let TensorMetadata { shape: tensor_shape, permutation } = parse_tensor_metadata(metadata)?;
+    match array.data_type() {
+        ArrowDataType::FixedSizeList(_, size) if *size as usize == tensor_shape.iter().product::<usize>() => {}
+        other => return Err(ArrowError::InvalidTensor(format!("storage type {} does not match shape {:?}", other, tensor_shape))),
+    }
+    let mut shape = vec![array.len()];
+    shape.extend_from_slice(&tensor_shape);
+    let sane = values_to_sane(list_values(array)?, shape)?;
+    let Some(permutation) = permutation else {
+        return Ok(sane);
+    };
+    let mut sorted = permutation.clone();
+    sorted.sort_unstable();
+    if !sorted.iter().copied().eq(0..tensor_shape.len()) {
+        return Err(ArrowError::InvalidTensor(format!("invalid permutation {:?}", permutation)));
+    }
+    // The stored shape is the physical one, whose axes are reordered by the permutation
+    let axes: Vec<usize> = std::iter::once(0).chain(permutation.iter().map(|axis| axis + 1)).collect();
+    Ok(crate::sane_dispatch!(
+        Sane: sane,
+        array => Sane::from(array.permuted_axes(axes).as_standard_layout().into_owned())
+    ))
+}
+
+/// The parts of the fixed shape tensor metadata that determine the array shape
+struct TensorMetadata {
+    shape: Vec<usize>,
+    permutation: Option<Vec<usize>>,
+}
+
+fn parse_tensor_metadata(metadata: &str) -> Result<TensorMetadata, ArrowError> {
+    let metadata: serde_json::Value = serde_json::from_str(metadata)
+        .map_err(|err| ArrowError::InvalidTensor(format!("invalid metadata: {}", err)))?;
+    let dims = |key: &str| -> Result<Option<Vec<usize>>, ArrowError> {
+        let Some(value) = metadata.get(key) else {
+            return Ok(None);
+        };
+        value.as_array()
+            .and_then(|items| items.iter().map(|item| item.as_u64().and_then(|dim| usize::try_from(dim).ok())).collect())
+            .map(Some)
+            .ok_or_else(|| ArrowError::InvalidTensor(format!("{} is not a list of integers", key)))
+    };
+    let shape = dims("shape")?.ok_or_else(|| ArrowError::InvalidTensor("missing shape".to_string()))?;
+    let permutation = dims("permutation")?;
+    if permutation.as_ref().is_some_and(|permutation| permutation.len() != shape.len()) {
+        return Err(ArrowError::InvalidTensor("permutation does not match shape".to_string()));
+    }
+    Ok(TensorMetadata { shape, permutation })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use arrow_array::{Array, ArrayRef, FixedSizeListArray, Float32Array, Int32Array};
+    use arrow_schema::{DataType as ArrowDataType, Field};
+    use ndarray::{array, s, Array3, ArrayD, IxDyn};
+
+    use super::{from_arrow, from_arrow_tensor, to_arrow, to_arrow_tensor, ArrowError, FIXED_SHAPE_TENSOR};
+    use crate::Sane;
+
+    #[test]
+    fn roundtrip_nested_lists() {
+        let array = Array3::from_shape_fn((2, 3, 4), |(i, j, k)| (i * 100 + j * 10 + k) as u16);
+        let arrow = to_arrow(Sane::from(array.clone())).unwrap();
+        assert_eq!(arrow.len(), 2);
+        assert!(matches!(arrow.data_type(), ArrowDataType::FixedSizeList(_, 3)));
+        assert_eq!(from_arrow(arrow).unwrap(), Sane::from(array.into_dyn()));
+
+        let empty = ArrayD::<bool>::from_elem(IxDyn(&[3, 0]), false);
+        let arrow = to_arrow(Sane::from(empty.clone())).unwrap();
+        assert_eq!(arrow.len(), 3);
+        assert_eq!(from_arrow(arrow).unwrap(), Sane::from(empty));
+    }
+
+    #[test]
+    fn zero_copy() {
+        let values = vec![1.0f32, 2.0, 3.0];
+        let ptr = values.as_ptr();
+        let arrow = to_arrow(Sane::from(ndarray::Array1::from(values))).unwrap();
+        let primitive = arrow.as_any().downcast_ref::<Float32Array>().unwrap();
+        assert_eq!(primitive.values().as_ptr(), ptr);
+        match from_arrow(arrow).unwrap() {
+            Sane::ArrayF32(array) => assert_eq!(array.as_ptr(), ptr),
+            _ => panic!("expected an f32 array"),
+        }
+    }
+
+    #[test]
+    fn sliced_arrays() {
+        let array = array![[1i64, 2, 3], [4, 5, 6], [7, 8, 9]];
+        let sliced = array.clone().slice_move(s![1.., ..]);
+        let arrow = to_arrow(Sane::from(sliced.clone())).unwrap();
+        assert_eq!(from_arrow(arrow.slice(1, 1)).unwrap(), Sane::from(sliced.slice(s![1.., ..]).to_owned().into_dyn()));
+
+        let transposed = array.reversed_axes();
+        let arrow = to_arrow(Sane::from(transposed.clone())).unwrap();
+        assert_eq!(from_arrow(arrow).unwrap(), Sane::from(transposed.into_dyn()));
+    }
+
+    #[test]
+    fn tensors() {
+        let array = Array3::from_shape_fn((2, 2, 3), |(i, j, k)| (i * 100 + j * 10 + k) as i32);
+        let (field, arrow) = to_arrow_tensor("images", Sane::from(array.clone())).unwrap();
+        assert_eq!(field.extension_type_name(), Some(FIXED_SHAPE_TENSOR));
+        assert_eq!(field.extension_type_metadata(), Some(r#"{"shape":[2,3]}"#));
+        assert!(matches!(arrow.data_type(), ArrowDataType::FixedSizeList(_, 6)));
+        assert_eq!(from_arrow_tensor(&field, arrow).unwrap(), Sane::from(array.clone().into_dyn()));
+
+        // Tensors stored as [3, 2] with logical shape [2, 3]
+        let physical = array.clone().permuted_axes([0, 2, 1]).as_standard_layout().into_owned();
+        let values: ArrayRef = Arc::new(Int32Array::from(physical.into_raw_vec()));
+        let item = Arc::new(Field::new_list_field(ArrowDataType::Int32, false));
+        let storage: ArrayRef = Arc::new(FixedSizeListArray::new(item, 6, values, None));
+        let field = Field::new("images", storage.data_type().clone(), false).with_metadata([
+            ("ARROW:extension:name".to_string(), FIXED_SHAPE_TENSOR.to_string()),
+            ("ARROW:extension:metadata".to_string(), r#"{ "shape": [3, 2], "permutation": [1, 0] }"#.to_string()),
+        ].into());
+        let expected = Sane::from(array.into_dyn());
+        assert_eq!(from_arrow_tensor(&field, storage.clone()).unwrap(), expected);
+
+        // Escaped quotes in dimension names do not end the string early
+        let metadata = r#"{"dim_names": ["x\"]", "y"], "shape": [3, 2], "permutation": [1, 0]}"#;
+        let field = field.with_metadata([
+            ("ARROW:extension:name".to_string(), FIXED_SHAPE_TENSOR.to_string()),
+            ("ARROW:extension:metadata".to_string(), metadata.to_string()),
+        ].into());
+        assert_eq!(from_arrow_tensor(&field, storage).unwrap(), expected);
+    }
+
+    #[test]
+    fn rejects_nulls() {
+        let arrow: ArrayRef = Arc::new(Int32Array::from(vec![Some(1), None]));
+        assert!(matches!(from_arrow(arrow), Err(ArrowError::Nulls)));
+        let scalar = Sane::from(ndarray::arr0(1u8).into_dyn());
+        assert!(matches!(to_arrow(scalar), Err(ArrowError::ZeroDimensional)));
+    }
+}
@@ -0,0 +1,297 @@
+//! Reading and writing arrays as [CBOR](https://www.rfc-editor.org/rfc/rfc8949) multi-dimensional
+//! arrays
+//!
+//! Arrays are written as row-major multi-dimensional arrays (tag 40) whose elements are a
+//! little-endian typed array, as defined by [RFC 8746](https://www.rfc-editor.org/rfc/rfc8746).
+//! Boolean arrays, which have no typed array, hold a plain array of booleans instead. `bf16` and
+//! complex arrays have no CBOR equivalent.
+//!
+//! Reading also accepts big-endian typed arrays and column-major multi-dimensional arrays (tag
+//! 1040). Only definite-length items are supported.
+use std::io::{Read, Write};
+
+use ndarray::{ArrayD, IxDyn};
+
+use crate::data::{DataType, Sane, SaneView};
+use crate::raw::{read_raw, write_raw, Endianness};
+use crate::read::ParseError;
+
+/// The tag of row-major multi-dimensional arrays
+const MULTI_DIMENSIONAL_ARRAY: u64 = 40;
+/// The tag of column-major multi-dimensional arrays
+const COLUMN_MAJOR_ARRAY: u64 = 1040;
+
+const UNSIGNED: u8 = 0;
+const BYTE_STRING: u8 = 2;
+const ARRAY: u8 = 4;
+const TAG: u8 = 6;
+const SIMPLE: u8 = 7;
+
+const FALSE: u64 = 20;
+const TRUE: u64 = 21;
+
+#[derive(Debug)]
+pub enum CborError {
+    Io(std::io::Error),
+    InvalidCbor(String),
+    UnsupportedTag(u64),
+    UnsupportedDataType(DataType),
+    Parse(ParseError),
+}
+
+impl std::fmt::Display for CborError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        use CborError::*;
+        match self {
+            Io(err) => write!(f, "I/O error: {}", err),
+            InvalidCbor(reason) => write!(f, "Invalid CBOR array: {}", reason),
+            UnsupportedTag(tag) => write!(f, "Unsupported CBOR tag {}", tag),
+            UnsupportedDataType(data_type) => write!(f, "Data type {} has no CBOR typed array", data_type),
+            Parse(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for CborError {}
+
+/// The tag of the little-endian typed array of a data type
+fn typed_array_tag(data_type: DataType) -> Option<u64> {
+    use DataType::*;
+    match data_type {
+        U8 => Some(64),
+        U16 => Some(69),
+        U32 => Some(70),
+        U64 => Some(71),
+        I8 => Some(72),
+        I16 => Some(77),
+        I32 => Some(78),
+        I64 => Some(79),
+        F16 => Some(84),
+        F32 => Some(85),
+        F64 => Some(86),
+        BF16 | C64 | C128 | Bool => None,
+    }
+}
+
+/// The data type and byte order of a typed array tag
+fn typed_array_type(tag: u64) -> Option<(DataType, Endianness)> {
+    use DataType::*;
+    let data_type = match tag {
+        // Tag 68 holds clamped rather than wrapped values, which makes no difference when reading
+        64 | 68 => U8,
+        65 | 69 => U16,
+        66 | 70 => U32,
+        67 | 71 => U64,
+        72 => I8,
+        73 | 77 => I16,
+        74 | 78 => I32,
+        75 | 79 => I64,
+        80 | 84 => F16,
+        81 | 85 => F32,
+        82 | 86 => F64,
+        _ => return None,
+    };
+    let endianness = if tag & 0b100 != 0 { Endianness::Little } else { Endianness::Big };
+    Some((data_type, endianness))
+}
+
+/// Append the initial byte and argument of a data item
+fn write_head(out: &mut Vec<u8>, major: u8, value: u64) {
+    let major = major << 5;
+    if value < 24 {
+        out.push(major | value as u8);
+    } else if let Ok(value) = u8::try_from(value) {
+        out.push(major | 24);
+        out.push(value);
+    } else if let Ok(value) = u16::try_from(value) {
+        out.push(major | 25);
+        out.extend_from_slice(&value.to_be_bytes());
+    } else if let Ok(value) = u32::try_from(value) {
+        out.push(major | 26);
+        out.extend_from_slice(&value.to_be_bytes());
+    } else {
+        out.push(major | 27);
+        out.extend_from_slice(&value.to_be_bytes());
+    }
+}
+
+/// Read the major type and argument of a data item
+fn read_head<R: Read>(reader: &mut R) -> Result<(u8, u64), CborError> {
+    let mut initial = [0u8];
+    reader.read_exact(&mut initial).map_err(CborError::Io)?;
+    let major = initial[0] >> 5;
+    let argument_len = match initial[0] & 0x1f {
+        info @ 0..=23 => return Ok((major, u64::from(info))),
+        24 => 1,
+        25 => 2,
+        26 => 4,
+        27 => 8,
+        31 => return Err(CborError::InvalidCbor("indefinite-length items are not supported".to_string())),
+        info => return Err(CborError::InvalidCbor(format!("reserved additional information {}", info))),
+    };
+    let mut argument = [0u8; 8];
+    reader.read_exact(&mut argument[8 - argument_len..]).map_err(CborError::Io)?;
+    Ok((major, u64::from_be_bytes(argument)))
+}
+
+/// Read the argument of a data item that must have the given major type
+fn expect_head<R: Read>(reader: &mut R, major: u8, expected: &str) -> Result<u64, CborError> {
+    match read_head(reader)? {
+        (actual, value) if actual == major => Ok(value),
+        _ => Err(CborError::InvalidCbor(format!("expected {}", expected))),
+    }
+}
+
+fn to_usize(value: u64) -> Result<usize, CborError> {
+    usize::try_from(value).map_err(|err| CborError::Parse(ParseError::CannotConvertToUSize(err)))
+}
+
+/// Write an array as a CBOR multi-dimensional array
+pub fn write_cbor<'a, W: Write, S: Into<SaneView<'a>>>(writer: &mut W, sane: S) -> Result<(), CborError> {
+    let sane = sane.into();
+    let mut head = vec![];
+    write_head(&mut head, TAG, MULTI_DIMENSIONAL_ARRAY);
+    write_head(&mut head, ARRAY, 2);
+    write_head(&mut head, ARRAY, sane.shape().len() as u64);
+    for &dim in sane.shape() {
+        write_head(&mut head, UNSIGNED, dim as u64);
+    }
+    if let SaneView::ArrayBool(array) = &sane {
+        write_head(&mut head, ARRAY, array.len() as u64);
+        for &value in array {
+            write_head(&mut head, SIMPLE, if value { TRUE } else { FALSE });
+        }
+        return writer.write_all(&head).map_err(CborError::Io);
+    }
+    let tag = typed_array_tag(sane.data_type()).ok_or(CborError::UnsupportedDataType(sane.data_type()))?;
+    write_head(&mut head, TAG, tag);
+    write_head(&mut head, BYTE_STRING, (sane.shape().iter().product::<usize>() * sane.data_type().size_in_bytes()) as u64);
+    writer.write_all(&head).map_err(CborError::Io)?;
+    write_raw(writer, sane, Endianness::Little).map_err(|err| match err {
+        crate::write::WriteError::Failed(err) => CborError::Io(err),
+        err => CborError::InvalidCbor(err.to_string()),
+    })
+}
+
+/// Read a CBOR multi-dimensional array holding a typed array or a plain array of booleans
+pub fn read_cbor<R: Read>(reader: &mut R) -> Result<Sane, CborError> {
+    let column_major = match expect_head(reader, TAG, "a multi-dimensional array tag")? {
+        MULTI_DIMENSIONAL_ARRAY => false,
+        COLUMN_MAJOR_ARRAY => true,
+        tag => return Err(CborError::UnsupportedTag(tag)),
+    };
+    if expect_head(reader, ARRAY, "an array of dimensions and elements")? != 2 {
+        return Err(CborError::InvalidCbor("expected an array of dimensions and elements".to_string()));
+    }
+    let ndim = expect_head(reader, ARRAY, "an array of dimensions")?;
+    let mut shape = vec![];
+    for _ in 0..ndim {
+        shape.push(to_usize(expect_head(reader, UNSIGNED, "an unsigned dimension")?)?);
+    }
+    // Column-major data is row-major data of the transposed array
+    if column_major {
+        shape.reverse();
+    }
+    let len = shape.iter()
+        .try_fold(1usize, |len, &dim| len.checked_mul(dim))
+        .ok_or_else(|| CborError::InvalidCbor("array too large".to_string()))?;
+    let sane = match read_head(reader)? {
+        (TAG, tag) => {
+            let (data_type, endianness) = typed_array_type(tag).ok_or(CborError::UnsupportedTag(tag))?;
+            let byte_len = expect_head(reader, BYTE_STRING, "a typed array")?;
+            if len.checked_mul(data_type.size_in_bytes()).map(|n| n as u64) != Some(byte_len) {
+                return Err(CborError::InvalidCbor(format!("typed array of {} bytes does not match the shape", byte_len)));
+            }
+            // Read the bytes before allocating for them, in case the length is bogus
+            let mut data = vec![];
+            reader.take(byte_len).read_to_end(&mut data).map_err(CborError::Io)?;
+            if data.len() as u64 != byte_len {
+                return Err(CborError::Io(std::io::ErrorKind::UnexpectedEof.into()));
+            }
+            read_raw(&mut data.as_slice(), &shape, data_type, endianness).map_err(CborError::Parse)?
+        }
+        (ARRAY, count) if count == len as u64 => {
+            let mut values = vec![];
+            for _ in 0..len {
+                match read_head(reader)? {
+                    (SIMPLE, FALSE) => values.push(false),
+                    (SIMPLE, TRUE) => values.push(true),
+                    _ => return Err(CborError::InvalidCbor("plain arrays must hold booleans".to_string())),
+                }
+            }
+            let array = ArrayD::from_shape_vec(IxDyn(&shape), values)
+                .map_err(|err| CborError::Parse(ParseError::ShapeError(err)))?;
+            Sane::from(array)
+        }
+        _ => return Err(CborError::InvalidCbor("expected a typed array or an array matching the shape".to_string())),
+    };
+    if column_major {
+        Ok(crate::sane_dispatch!(Sane: sane, array => {
+            Sane::from(array.reversed_axes().as_standard_layout().into_owned())
+        }))
+    } else {
+        Ok(sane)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ndarray::array;
+
+    use super::{read_cbor, write_cbor, CborError};
+    use crate::Sane;
+
+    /// The uint16 example of RFC 8746, section 3.1.1
+    const RFC_EXAMPLE: &[u8] = &[
+        0xd8, 0x28, 0x82, 0x82, 0x02, 0x03, 0xd8, 0x45, 0x4c,
+        0x02, 0x00, 0x04, 0x00, 0x08, 0x00, 0x04, 0x00, 0x10, 0x00, 0x00, 0x01,
+    ];
+
+    #[test]
+    fn rfc_example() {
+        let sane = Sane::from(array![[2u16, 4, 8], [4, 16, 256]]);
+        let mut buffer = vec![];
+        write_cbor(&mut buffer, &sane).unwrap();
+        assert_eq!(buffer, RFC_EXAMPLE);
+        assert_eq!(read_cbor(&mut { RFC_EXAMPLE }).unwrap(), sane);
+    }
+
+    #[test]
+    fn roundtrip_cbor() {
+        let arrays = [
+            Sane::from(ndarray::Array::linspace(-1.0f64, 1.0, 30).into_shape((2, 3, 5)).unwrap()),
+            Sane::from(array![[true, false, true]]),
+            Sane::from(ndarray::arr0(-7i8).into_dyn()),
+            Sane::from(ndarray::Array2::<i64>::zeros((0, 4))),
+        ];
+        for sane in arrays {
+            let mut buffer = vec![];
+            write_cbor(&mut buffer, &sane).unwrap();
+            assert_eq!(read_cbor(&mut buffer.as_slice()).unwrap(), sane);
+        }
+    }
+
+    #[test]
+    fn big_endian_column_major() {
+        // Tag 1040 holding a [2, 2] big-endian uint16 array
+        let data = [0xd9, 0x04, 0x10, 0x82, 0x82, 0x02, 0x02, 0xd8, 0x41, 0x48, 0, 1, 0, 2, 0, 3, 1, 0];
+        let sane = read_cbor(&mut data.as_slice()).unwrap();
+        assert_eq!(sane, Sane::from(array![[1u16, 3], [2, 256]]));
+    }
+
+    #[test]
+    fn invalid_input() {
+        let wrong_length = [0xd8, 0x28, 0x82, 0x81, 0x02, 0xd8, 0x40, 0x41, 0];
+        assert!(matches!(read_cbor(&mut wrong_length.as_slice()), Err(CborError::InvalidCbor(_))));
+        // Shape [2^40] and a plain array of 2^40 booleans, truncated
+        let oversized = [
+            0xd8, 0x28, 0x82, 0x81, 0x1b, 0, 0, 1, 0, 0, 0, 0, 0,
+            0x9b, 0, 0, 1, 0, 0, 0, 0, 0,
+        ];
+        assert!(matches!(read_cbor(&mut oversized.as_slice()), Err(CborError::Io(_))));
+        let oversized_typed = [0xd8, 0x28, 0x82, 0x81, 0x1b, 0, 0, 1, 0, 0, 0, 0, 0, 0xd8, 0x40, 0x5b, 0, 0, 1, 0, 0, 0, 0, 0];
+        assert!(matches!(read_cbor(&mut oversized_typed.as_slice()), Err(CborError::Io(_))));
+        let other_tag = [0xc1, 0x00];
+        assert!(matches!(read_cbor(&mut other_tag.as_slice()), Err(CborError::UnsupportedTag(1))));
+    }
+}
@@ -1,8 +1,16 @@
-use ndarray::ArrayD;
-use quickcheck::{Arbitrary, Gen};
+use ndarray::{Array, ArrayBase, ArrayD, ArrayView, ArrayViewD, ArrayViewMut, ArrayViewMutD, Dimension, IxDyn, RawData};
+
+use crate::element::SaneElement;
+use crate::read::ParseError;
+use crate::write::encoded_len;
 
 /// SANE [supported data types](https://github.com/considerate/sane#data-types)
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+///
+/// The 16-bit types `I16`, `U16`, `F16` and `BF16`, the complex types `C64` and `C128` and `Bool`
+/// are an extension of this crate that is not part of the SANE specification. They use the type
+/// codes from 128 upwards, leaving the lower codes free for future versions of the specification.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
 pub enum DataType {
     F32,
     I32,
@@ -12,13 +20,174 @@ pub enum DataType {
     U64,
     I8,
     U8,
+    I16,
+    U16,
+    F16,
+    BF16,
+    /// Complex numbers with `f32` real and imaginary parts
+    C64,
+    /// Complex numbers with `f64` real and imaginary parts
+    C128,
+    /// Booleans stored as one byte each, which must be either 0 or 1
+    Bool,
+}
+
+impl DataType {
+    /// All data types, including those whose element types are not enabled in this build
+    pub const ALL: [DataType; 15] = [
+        DataType::F32,
+        DataType::I32,
+        DataType::U32,
+        DataType::F64,
+        DataType::I64,
+        DataType::U64,
+        DataType::I8,
+        DataType::U8,
+        DataType::I16,
+        DataType::U16,
+        DataType::F16,
+        DataType::BF16,
+        DataType::C64,
+        DataType::C128,
+        DataType::Bool,
+    ];
+
+    /// Number of bytes of a single element of this type
+    pub fn size_in_bytes(&self) -> usize {
+        use DataType::*;
+        match self {
+            I8 | U8 | Bool => 1,
+            I16 | U16 | F16 | BF16 => 2,
+            F32 | I32 | U32 => 4,
+            F64 | I64 | U64 | C64 => 8,
+            C128 => 16,
+        }
+    }
+
+    /// Whether this is a real floating point type
+    pub fn is_float(&self) -> bool {
+        use DataType::*;
+        matches!(self, F32 | F64 | F16 | BF16)
+    }
+
+    /// Whether this is a complex floating point type
+    pub fn is_complex(&self) -> bool {
+        matches!(self, DataType::C64 | DataType::C128)
+    }
+
+    /// Whether values of this type can be negative
+    pub fn is_signed(&self) -> bool {
+        use DataType::*;
+        match self {
+            F32 | I32 | F64 | I64 | I8 | I16 | F16 | BF16 | C64 | C128 => true,
+            U32 | U64 | U8 | U16 | Bool => false,
+        }
+    }
+
+    /// The short name of the type, such as `"f32"` or `"u8"`
+    pub fn name(&self) -> &'static str {
+        use DataType::*;
+        match self {
+            F32 => "f32",
+            I32 => "i32",
+            U32 => "u32",
+            F64 => "f64",
+            I64 => "i64",
+            U64 => "u64",
+            I8 => "i8",
+            U8 => "u8",
+            I16 => "i16",
+            U16 => "u16",
+            F16 => "f16",
+            BF16 => "bf16",
+            C64 => "c64",
+            C128 => "c128",
+            Bool => "bool",
+        }
+    }
+}
+
+impl std::fmt::Display for DataType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name())
+    }
 }
 
-impl Arbitrary for DataType {
-    fn arbitrary(gen: &mut Gen) -> Self {
+/// The error returned when parsing an unknown data type name
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseDataTypeError(pub String);
+
+impl std::fmt::Display for ParseDataTypeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Unknown data type name: {}", self.0)
+    }
+}
+
+impl std::error::Error for ParseDataTypeError {}
+
+/// Parses the names returned by [`DataType::name`] as well as NumPy names, either as dtype names
+/// such as `"float32"` or as little-endian array protocol type strings such as `"<f4"`.
+///
+/// Type strings need a byte order prefix (`<`, `|` or `=`), as `"i8"` and `"u8"` are taken to be
+/// the 8-bit types rather than NumPy's 8-byte ones.
+impl std::str::FromStr for DataType {
+    type Err = ParseDataTypeError;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
         use DataType::*;
-        let options = [F32, I32, U32, F64, I64, U64, I8, U8];
-        gen.choose(&options).unwrap().clone()
+        // Native byte order is taken to be little-endian, like SANE
+        let data_type = match name.strip_prefix(['<', '|', '=']) {
+            Some(type_string) => match type_string {
+                "f4" => Some(F32),
+                "i4" => Some(I32),
+                "u4" => Some(U32),
+                "f8" => Some(F64),
+                "i8" => Some(I64),
+                "u8" => Some(U64),
+                "i1" => Some(I8),
+                "u1" => Some(U8),
+                "i2" => Some(I16),
+                "u2" => Some(U16),
+                "f2" => Some(F16),
+                "c8" => Some(C64),
+                "c16" => Some(C128),
+                "b1" => Some(Bool),
+                _ => None,
+            },
+            None => match name {
+                "f32" | "float32" => Some(F32),
+                "i32" | "int32" => Some(I32),
+                "u32" | "uint32" => Some(U32),
+                "f64" | "float64" => Some(F64),
+                "i64" | "int64" => Some(I64),
+                "u64" | "uint64" => Some(U64),
+                "i8" | "int8" => Some(I8),
+                "u8" | "uint8" => Some(U8),
+                "i16" | "int16" => Some(I16),
+                "u16" | "uint16" => Some(U16),
+                "f16" | "float16" => Some(F16),
+                "bf16" | "bfloat16" => Some(BF16),
+                "c64" | "complex64" => Some(C64),
+                "c128" | "complex128" => Some(C128),
+                "bool" => Some(Bool),
+                _ => None,
+            },
+        };
+        data_type.ok_or_else(|| ParseDataTypeError(name.to_string()))
+    }
+}
+
+impl TryFrom<u8> for DataType {
+    type Error = u8;
+
+    fn try_from(code: u8) -> Result<Self, u8> {
+        parse_data_type(code)
+    }
+}
+
+impl From<DataType> for u8 {
+    fn from(data_type: DataType) -> u8 {
+        data_type_code(data_type)
     }
 }
 
@@ -33,6 +202,13 @@ pub fn parse_data_type(code: u8) -> Result<DataType, u8> {
         5 => Ok(DataType::U64),
         6 => Ok(DataType::I8),
         7 => Ok(DataType::U8),
+        128 => Ok(DataType::I16),
+        129 => Ok(DataType::U16),
+        130 => Ok(DataType::F16),
+        131 => Ok(DataType::BF16),
+        132 => Ok(DataType::C64),
+        133 => Ok(DataType::C128),
+        134 => Ok(DataType::Bool),
         n => Err(n),
     }
 }
@@ -48,12 +224,30 @@ pub fn data_type_code(data_type: DataType) -> u8 {
         DataType::U64 => 5,
         DataType::I8 => 6,
         DataType::U8 => 7,
+        DataType::I16 => 128,
+        DataType::U16 => 129,
+        DataType::F16 => 130,
+        DataType::BF16 => 131,
+        DataType::C64 => 132,
+        DataType::C128 => 133,
+        DataType::Bool => 134,
     }
 }
 
+/// The data type of the elements of an array
+fn element_data_type<A: SaneElement, S: RawData<Elem = A>>(_array: &ArrayBase<S, IxDyn>) -> DataType {
+    A::DATA_TYPE
+}
+
 /// A Sane array is an array with dynamic shape and elements of one of the [supported data
 /// types](https://github.com/considerate/sane#data-types)
+///
+/// The `ArrayF16` and `ArrayBF16` variants only exist with the `half` feature and the `ArrayC64`
+/// and `ArrayC128` variants with the `num-complex` feature, so a `match` outside this crate needs a
+/// wildcard arm. [`sane_dispatch!`](crate::sane_dispatch) covers every variant
+/// of the build.
 #[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
 pub enum Sane {
     ArrayF32(ArrayD<f32>),
     ArrayI32(ArrayD<i32>),
@@ -63,78 +257,239 @@ pub enum Sane {
     ArrayU64(ArrayD<u64>),
     ArrayI8(ArrayD<i8>),
     ArrayU8(ArrayD<u8>),
+    ArrayI16(ArrayD<i16>),
+    ArrayU16(ArrayD<u16>),
+    ArrayBool(ArrayD<bool>),
+    #[cfg(feature = "half")]
+    ArrayF16(ArrayD<half::f16>),
+    #[cfg(feature = "half")]
+    ArrayBF16(ArrayD<half::bf16>),
+    #[cfg(feature = "num-complex")]
+    ArrayC64(ArrayD<num_complex::Complex<f32>>),
+    #[cfg(feature = "num-complex")]
+    ArrayC128(ArrayD<num_complex::Complex<f64>>),
 }
 
+impl Sane {
+    /// The shape of the array
+    pub fn shape(&self) -> &[usize] {
+        crate::sane_dispatch!(Sane: self, array => array.shape())
+    }
 
-/// The header of a SANE array, consisting of the shape, the data type and the length of the data
-/// in number of bytes
-pub struct Header {
-    pub shape: Vec<usize>,
-    pub data_type: DataType,
-    pub data_length: usize,
-}
+    /// The number of dimensions of the array
+    pub fn ndim(&self) -> usize {
+        crate::sane_dispatch!(Sane: self, array => array.ndim())
+    }
+
+    /// The number of elements in the array
+    pub fn len(&self) -> usize {
+        crate::sane_dispatch!(Sane: self, array => array.len())
+    }
+
+    /// Whether the array has no elements
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The data type of the elements of the array
+    pub fn data_type(&self) -> DataType {
+        crate::sane_dispatch!(Sane: self, array => element_data_type(array))
+    }
+
+    /// The number of bytes of the encoded elements, excluding the header
+    pub fn byte_len(&self) -> usize {
+        self.len() * self.data_type().size_in_bytes()
+    }
 
-/// An element type with the [`SaneData`] trait defines which of the [supported data
-/// types](https://github.com/considerate/sane#data-types) it corresponds to
-pub trait SaneData: Copy {
-    fn sane_data_type() -> DataType;
+    /// Length in bytes of the SANE encoding of this array, including its header
+    pub fn encoded_len(&self) -> usize {
+        self.view().encoded_len()
+    }
+
+    /// Borrow the array as a [`SaneView`]
+    pub fn view(&self) -> SaneView<'_> {
+        crate::sane_dispatch!(Sane: self, array => SaneView::from(array.view()))
+    }
+
+    /// Mutably borrow the array as a [`SaneViewMut`]
+    pub fn view_mut(&mut self) -> SaneViewMut<'_> {
+        crate::sane_dispatch!(Sane: self, array => SaneViewMut::from(array.view_mut()))
+    }
+
+    /// Convert into an array with elements of type `A`, or give back the array if its elements
+    /// are of another type
+    pub fn into_typed<A: SaneElement>(self) -> Result<ArrayD<A>, Sane> {
+        A::from_sane(self)
+    }
+
+    /// Borrow as an array with elements of type `A`, if that is the type of its elements
+    pub fn as_typed<A: SaneElement>(&self) -> Option<&ArrayD<A>> {
+        A::sane_ref(self)
+    }
+
+    /// Mutably borrow as an array with elements of type `A`, if that is the type of its elements
+    pub fn as_typed_mut<A: SaneElement>(&mut self) -> Option<&mut ArrayD<A>> {
+        A::sane_mut(self)
+    }
 }
 
-impl SaneData for f32 {
-    fn sane_data_type()  -> DataType {
-        DataType::F32
+impl<A: SaneElement, D: Dimension> From<Array<A, D>> for Sane {
+    fn from(array: Array<A, D>) -> Self {
+        A::into_sane(array.into_dyn())
     }
 }
 
-impl SaneData for i32 {
-    fn sane_data_type()  -> DataType {
-        DataType::I32
+/// Fails with [`ParseError::WrongDataType`] if the elements are of another type and with
+/// [`ParseError::ShapeError`] if the array doesn't have `D` dimensions
+impl<A: SaneElement, D: Dimension> TryFrom<Sane> for Array<A, D> {
+    type Error = ParseError;
+
+    fn try_from(sane: Sane) -> Result<Self, ParseError> {
+        let array = sane.into_typed::<A>().map_err(|sane| ParseError::WrongDataType(sane.data_type()))?;
+        array.into_dimensionality().map_err(ParseError::ShapeError)
     }
 }
 
-impl SaneData for u32 {
-    fn sane_data_type()  -> DataType {
-        DataType::U32
+/// A borrowed view of an array with dynamic shape and elements of one of the [supported data
+/// types](https://github.com/considerate/sane#data-types)
+///
+/// Like [`Sane`], it has variants that depend on the enabled features.
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub enum SaneView<'a> {
+    ArrayF32(ArrayViewD<'a, f32>),
+    ArrayI32(ArrayViewD<'a, i32>),
+    ArrayU32(ArrayViewD<'a, u32>),
+    ArrayF64(ArrayViewD<'a, f64>),
+    ArrayI64(ArrayViewD<'a, i64>),
+    ArrayU64(ArrayViewD<'a, u64>),
+    ArrayI8(ArrayViewD<'a, i8>),
+    ArrayU8(ArrayViewD<'a, u8>),
+    ArrayI16(ArrayViewD<'a, i16>),
+    ArrayU16(ArrayViewD<'a, u16>),
+    ArrayBool(ArrayViewD<'a, bool>),
+    #[cfg(feature = "half")]
+    ArrayF16(ArrayViewD<'a, half::f16>),
+    #[cfg(feature = "half")]
+    ArrayBF16(ArrayViewD<'a, half::bf16>),
+    #[cfg(feature = "num-complex")]
+    ArrayC64(ArrayViewD<'a, num_complex::Complex<f32>>),
+    #[cfg(feature = "num-complex")]
+    ArrayC128(ArrayViewD<'a, num_complex::Complex<f64>>),
+}
+
+impl<'a> SaneView<'a> {
+    /// The shape of the array
+    pub fn shape(&self) -> &[usize] {
+        crate::sane_dispatch!(SaneView: self, array => array.shape())
+    }
+
+    /// The data type of the elements of the array
+    pub fn data_type(&self) -> DataType {
+        crate::sane_dispatch!(SaneView: self, array => element_data_type(array))
+    }
+
+    /// Copy the viewed elements into an owned [`Sane`] array
+    pub fn to_owned(&self) -> Sane {
+        crate::sane_dispatch!(SaneView: self, array => Sane::from(array.to_owned()))
+    }
+
+    /// Length in bytes of the SANE encoding of this array, including its header
+    pub fn encoded_len(&self) -> usize {
+        crate::sane_dispatch!(SaneView: self, array => encoded_len(array))
     }
 }
 
-impl SaneData for f64 {
-    fn sane_data_type()  -> DataType {
-        DataType::F64
+impl<'a> From<&'a Sane> for SaneView<'a> {
+    fn from(sane: &'a Sane) -> Self {
+        sane.view()
     }
 }
 
-impl SaneData for i64 {
-    fn sane_data_type()  -> DataType {
-        DataType::I64
+impl<'a, A: SaneElement, D: Dimension> From<ArrayView<'a, A, D>> for SaneView<'a> {
+    fn from(array: ArrayView<'a, A, D>) -> Self {
+        A::into_sane_view(array.into_dyn())
     }
 }
 
-impl SaneData for u64 {
-    fn sane_data_type()  -> DataType {
-        DataType::U64
+/// A mutably borrowed view of an array with dynamic shape and elements of one of the [supported
+/// data types](https://github.com/considerate/sane#data-types)
+///
+/// Like [`Sane`], it has variants that depend on the enabled features.
+#[derive(Debug, PartialEq)]
+#[non_exhaustive]
+pub enum SaneViewMut<'a> {
+    ArrayF32(ArrayViewMutD<'a, f32>),
+    ArrayI32(ArrayViewMutD<'a, i32>),
+    ArrayU32(ArrayViewMutD<'a, u32>),
+    ArrayF64(ArrayViewMutD<'a, f64>),
+    ArrayI64(ArrayViewMutD<'a, i64>),
+    ArrayU64(ArrayViewMutD<'a, u64>),
+    ArrayI8(ArrayViewMutD<'a, i8>),
+    ArrayU8(ArrayViewMutD<'a, u8>),
+    ArrayI16(ArrayViewMutD<'a, i16>),
+    ArrayU16(ArrayViewMutD<'a, u16>),
+    ArrayBool(ArrayViewMutD<'a, bool>),
+    #[cfg(feature = "half")]
+    ArrayF16(ArrayViewMutD<'a, half::f16>),
+    #[cfg(feature = "half")]
+    ArrayBF16(ArrayViewMutD<'a, half::bf16>),
+    #[cfg(feature = "num-complex")]
+    ArrayC64(ArrayViewMutD<'a, num_complex::Complex<f32>>),
+    #[cfg(feature = "num-complex")]
+    ArrayC128(ArrayViewMutD<'a, num_complex::Complex<f64>>),
+}
+
+impl<'a> SaneViewMut<'a> {
+    /// Reborrow as a read-only [`SaneView`]
+    pub fn view(&self) -> SaneView<'_> {
+        crate::sane_dispatch!(SaneViewMut: self, array => SaneView::from(array.view()))
     }
 }
 
-impl SaneData for i8 {
-    fn sane_data_type()  -> DataType {
-        DataType::I8
+impl<'a> From<&'a mut Sane> for SaneViewMut<'a> {
+    fn from(sane: &'a mut Sane) -> Self {
+        sane.view_mut()
     }
 }
 
-impl SaneData for u8 {
-    fn sane_data_type()  -> DataType {
-        DataType::U8
+impl<'a, A: SaneElement, D: Dimension> From<ArrayViewMut<'a, A, D>> for SaneViewMut<'a> {
+    fn from(array: ArrayViewMut<'a, A, D>) -> Self {
+        A::into_sane_view_mut(array.into_dyn())
     }
 }
 
+/// The header of a SANE array, consisting of the shape, the data type and the length of the data
+/// in number of bytes
+pub struct Header {
+    pub shape: Vec<usize>,
+    pub data_type: DataType,
+    pub data_length: usize,
+}
+
 #[cfg(test)]
 mod tests {
     use super::{DataType, parse_data_type, data_type_code};
     use quickcheck::quickcheck;
+
+    #[test]
+    fn numpy_names() {
+        assert_eq!("float32".parse(), Ok(DataType::F32));
+        assert_eq!("<f4".parse(), Ok(DataType::F32));
+        assert_eq!("<i8".parse(), Ok(DataType::I64));
+        assert_eq!("|u1".parse(), Ok(DataType::U8));
+        assert_eq!("|b1".parse(), Ok(DataType::Bool));
+        assert_eq!("<c16".parse(), Ok(DataType::C128));
+        assert!(">f4".parse::<DataType>().is_err());
+    }
+
     quickcheck! {
         fn prop_data_type_round(data_type: DataType) -> bool {
-            Ok(data_type.clone()) == parse_data_type(data_type_code(data_type))
+            Ok(data_type) == parse_data_type(data_type_code(data_type))
+        }
+
+        fn prop_data_type_name_round(data_type: DataType) -> bool {
+            Ok(data_type) == data_type.to_string().parse()
         }
     }
 }
@@ -0,0 +1,221 @@
+use bytemuck::{NoUninit, Zeroable};
+use ndarray::{ArrayD, ArrayViewD, ArrayViewMutD};
+
+use crate::data::{DataType, Sane, SaneView, SaneViewMut};
+use crate::read::ParseError;
+
+mod private {
+    pub trait Sealed {}
+}
+
+/// An element type of a SANE array, corresponding to one of the [supported data
+/// types](https://github.com/considerate/sane#data-types).
+///
+/// Elements are converted in bulk between slices of values and their little-endian encoding. On
+/// little-endian targets this is a plain copy of the bytes.
+///
+/// This trait is sealed: it is implemented for the supported element types only.
+pub trait SaneElement: Copy + Zeroable + NoUninit + private::Sealed {
+    /// The SANE data type of this element
+    const DATA_TYPE: DataType;
+
+    /// Number of bytes of a single encoded element
+    const SIZE: usize = std::mem::size_of::<Self>();
+
+    /// Encode `src` as little-endian bytes into `dst`, which must be exactly
+    /// `src.len() * Self::SIZE` bytes long
+    fn encode_le(src: &[Self], dst: &mut [u8]);
+
+    /// Decode the little-endian bytes in `src` into `dst`, where `src` must be exactly
+    /// `dst.len() * Self::SIZE` bytes long
+    fn decode_le(src: &[u8], dst: &mut [Self]) -> Result<(), ParseError>;
+
+    #[doc(hidden)]
+    fn into_sane(array: ArrayD<Self>) -> Sane;
+
+    #[doc(hidden)]
+    fn from_sane(sane: Sane) -> Result<ArrayD<Self>, Sane>;
+
+    #[doc(hidden)]
+    fn sane_ref(sane: &Sane) -> Option<&ArrayD<Self>>;
+
+    #[doc(hidden)]
+    fn sane_mut(sane: &mut Sane) -> Option<&mut ArrayD<Self>>;
+
+    #[doc(hidden)]
+    fn into_sane_view(array: ArrayViewD<'_, Self>) -> SaneView<'_>;
+
+    #[doc(hidden)]
+    fn into_sane_view_mut(array: ArrayViewMutD<'_, Self>) -> SaneViewMut<'_>;
+}
+
+// Wrapping and unwrapping of the variant of `Sane`, `SaneView` and `SaneViewMut` that holds
+// elements of the implementing type
+macro_rules! variant_methods {
+    ($variant:ident) => {
+        fn into_sane(array: ArrayD<Self>) -> Sane {
+            Sane::$variant(array)
+        }
+
+        fn from_sane(sane: Sane) -> Result<ArrayD<Self>, Sane> {
+            match sane {
+                Sane::$variant(array) => Ok(array),
+                #[allow(unreachable_patterns)]
+                other => Err(other),
+            }
+        }
+
+        fn sane_ref(sane: &Sane) -> Option<&ArrayD<Self>> {
+            match sane {
+                Sane::$variant(array) => Some(array),
+                #[allow(unreachable_patterns)]
+                _ => None,
+            }
+        }
+
+        fn sane_mut(sane: &mut Sane) -> Option<&mut ArrayD<Self>> {
+            match sane {
+                Sane::$variant(array) => Some(array),
+                #[allow(unreachable_patterns)]
+                _ => None,
+            }
+        }
+
+        fn into_sane_view(array: ArrayViewD<'_, Self>) -> SaneView<'_> {
+            SaneView::$variant(array)
+        }
+
+        fn into_sane_view_mut(array: ArrayViewMutD<'_, Self>) -> SaneViewMut<'_> {
+            SaneViewMut::$variant(array)
+        }
+    }
+}
+
+/// The names of NaN and the infinities in JSON and other formats whose numbers cannot represent
+/// them, or `None` for finite values
+#[cfg(any(feature = "serde", feature = "json"))]
+pub(crate) fn non_finite_name(value: f64) -> Option<&'static str> {
+    if value.is_nan() {
+        Some("NaN")
+    } else if value.is_infinite() {
+        Some(if value > 0.0 { "Infinity" } else { "-Infinity" })
+    } else {
+        None
+    }
+}
+
+/// The value named by [`non_finite_name`]
+#[cfg(any(feature = "serde", feature = "json"))]
+pub(crate) fn parse_non_finite(name: &str) -> Option<f64> {
+    match name {
+        "NaN" => Some(f64::NAN),
+        "Infinity" => Some(f64::INFINITY),
+        "-Infinity" => Some(f64::NEG_INFINITY),
+        _ => None,
+    }
+}
+
+/// Reverse the byte order of each `unit`-sized chunk of `bytes`
+pub(crate) fn swap_bytes(bytes: &mut [u8], unit: usize) {
+    if unit > 1 {
+        for chunk in bytes.chunks_exact_mut(unit) {
+            chunk.reverse();
+        }
+    }
+}
+
+// Element types for which every bit pattern is valid. `$unit` is the size of the scalars the
+// element consists of, which is what needs byte swapping on big-endian targets.
+macro_rules! pod_element {
+    ($t:ty, $data_type:ident, $variant:ident, $unit:expr) => {
+        impl private::Sealed for $t {}
+
+        impl SaneElement for $t {
+            const DATA_TYPE: DataType = DataType::$data_type;
+
+            fn encode_le(src: &[Self], dst: &mut [u8]) {
+                dst.copy_from_slice(bytemuck::cast_slice(src));
+                if cfg!(target_endian = "big") {
+                    swap_bytes(dst, $unit);
+                }
+            }
+
+            fn decode_le(src: &[u8], dst: &mut [Self]) -> Result<(), ParseError> {
+                let bytes: &mut [u8] = bytemuck::cast_slice_mut(dst);
+                bytes.copy_from_slice(src);
+                if cfg!(target_endian = "big") {
+                    swap_bytes(bytes, $unit);
+                }
+                Ok(())
+            }
+
+            variant_methods!($variant);
+        }
+    }
+}
+
+pod_element!(f32, F32, ArrayF32, 4);
+pod_element!(i32, I32, ArrayI32, 4);
+pod_element!(u32, U32, ArrayU32, 4);
+pod_element!(f64, F64, ArrayF64, 8);
+pod_element!(i64, I64, ArrayI64, 8);
+pod_element!(u64, U64, ArrayU64, 8);
+pod_element!(i8, I8, ArrayI8, 1);
+pod_element!(u8, U8, ArrayU8, 1);
+pod_element!(i16, I16, ArrayI16, 2);
+pod_element!(u16, U16, ArrayU16, 2);
+#[cfg(feature = "half")]
+pod_element!(half::f16, F16, ArrayF16, 2);
+#[cfg(feature = "half")]
+pod_element!(half::bf16, BF16, ArrayBF16, 2);
+// Complex numbers are stored as interleaved pairs of real and imaginary parts
+#[cfg(feature = "num-complex")]
+pod_element!(num_complex::Complex<f32>, C64, ArrayC64, 4);
+#[cfg(feature = "num-complex")]
+pod_element!(num_complex::Complex<f64>, C128, ArrayC128, 8);
+
+impl private::Sealed for bool {}
+
+/// Booleans are stored as one byte each, and any byte other than 0 or 1 is rejected when reading
+impl SaneElement for bool {
+    const DATA_TYPE: DataType = DataType::Bool;
+
+    fn encode_le(src: &[Self], dst: &mut [u8]) {
+        dst.copy_from_slice(bytemuck::cast_slice(src));
+    }
+
+    fn decode_le(src: &[u8], dst: &mut [Self]) -> Result<(), ParseError> {
+        for (value, &byte) in dst.iter_mut().zip(src) {
+            *value = match byte {
+                0 => false,
+                1 => true,
+                _ => return Err(ParseError::InvalidBool(byte)),
+            };
+        }
+        Ok(())
+    }
+
+    variant_methods!(ArrayBool);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SaneElement;
+    use quickcheck::quickcheck;
+
+    quickcheck! {
+        fn prop_encode_matches_to_le_bytes(values: Vec<i64>) -> bool {
+            let mut bytes = vec![0; values.len() * i64::SIZE];
+            i64::encode_le(&values, &mut bytes);
+            let expected: Vec<u8> = values.iter().flat_map(|v| v.to_le_bytes()).collect();
+            bytes == expected
+        }
+
+        fn prop_decode_roundtrip(values: Vec<u16>) -> bool {
+            let mut bytes = vec![0; values.len() * u16::SIZE];
+            u16::encode_le(&values, &mut bytes);
+            let mut decoded = vec![0; values.len()];
+            u16::decode_le(&bytes, &mut decoded).is_ok() && decoded == values
+        }
+    }
+}
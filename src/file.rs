@@ -0,0 +1,147 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufReader, BufWriter, ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use ndarray::{Array, ArrayBase, Data, Dimension};
+
+use crate::element::SaneElement;
+use crate::read::{read_sane, read_sane_dyn, ParseError};
+use crate::write::{write_sane, write_sane_arrays, write_sane_arrays_dyn, WriteError};
+use crate::data::{Sane, SaneView};
+
+/// Parse a SANE-encoded file at `path` into an array with known type and rank
+pub fn read_sane_file<P: AsRef<Path>, A: SaneElement, D: Dimension>(
+    path: P,
+) -> Result<Array<A, D>, ParseError> {
+    let file = File::open(path).map_err(ParseError::ReadError)?;
+    read_sane(&mut BufReader::new(file))
+}
+
+/// Parse a SANE-encoded file at `path` into an array with dynamic type and rank
+pub fn read_sane_file_dyn<P: AsRef<Path>>(path: P) -> Result<Sane, ParseError> {
+    let file = File::open(path).map_err(ParseError::ReadError)?;
+    read_sane_dyn(&mut BufReader::new(file))
+}
+
+/// Write array into a SANE-encoded file at `path`, replacing it atomically
+pub fn write_sane_file<P: AsRef<Path>, A: SaneElement, D: Dimension, Repr>(
+    path: P,
+    array: &ArrayBase<Repr, D>,
+) -> Result<(), WriteError>
+where
+    Repr: Data<Elem = A>
+{
+    write_atomically(path.as_ref(), |file| write_sane(file, array))
+}
+
+/// Write multiple SANE-encoded arrays to a file at `path`, replacing it atomically
+pub fn write_sane_arrays_file<'a, P: AsRef<Path>, A: SaneElement + 'a, D: Dimension + 'a, Arrays, Repr>(
+    path: P,
+    arrays: Arrays,
+) -> Result<(), WriteError>
+where
+    Repr: Data<Elem = A> + 'a,
+    Arrays: IntoIterator<Item = &'a ArrayBase<Repr, D>>
+{
+    write_atomically(path.as_ref(), |file| write_sane_arrays(file, arrays))
+}
+
+/// Write multiple SANE-encoded arrays to a file at `path`, each with a dynamic shape and data
+/// type, replacing the file atomically
+pub fn write_sane_arrays_file_dyn<'a, P: AsRef<Path>, Arrays>(
+    path: P,
+    arrays: Arrays,
+) -> Result<(), WriteError>
+where
+    Arrays: IntoIterator,
+    Arrays::Item: Into<SaneView<'a>>
+{
+    write_atomically(path.as_ref(), |file| write_sane_arrays_dyn(file, arrays))
+}
+
+/// Used to give concurrent writers within this process distinct temporary files
+static TEMP_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// Create a new temporary file next to `path`
+fn create_temp_file(path: &Path) -> Result<(PathBuf, File), std::io::Error> {
+    let file_name = path.file_name().ok_or_else(|| {
+        std::io::Error::new(ErrorKind::InvalidInput, "path does not name a file")
+    })?;
+    loop {
+        let count = TEMP_COUNTER.fetch_add(1, Ordering::Relaxed);
+        let mut temp_name = std::ffi::OsString::from(".");
+        temp_name.push(file_name);
+        temp_name.push(format!(".{}.{}.tmp", std::process::id(), count));
+        let temp_path = path.with_file_name(temp_name);
+        match OpenOptions::new().write(true).create_new(true).open(&temp_path) {
+            Ok(file) => return Ok((temp_path, file)),
+            Err(err) if err.kind() == ErrorKind::AlreadyExists => continue,
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+/// Write to a temporary file in the same directory as `path`, sync it to disk and rename it to
+/// `path`, so that readers either see the old file or the complete new one.
+fn write_atomically<F>(path: &Path, write: F) -> Result<(), WriteError>
+where
+    F: FnOnce(&mut BufWriter<File>) -> Result<(), WriteError>
+{
+    let (temp_path, file) = create_temp_file(path).map_err(WriteError::Failed)?;
+    let result = (|| {
+        let mut writer = BufWriter::new(file);
+        write(&mut writer)?;
+        writer.flush().map_err(WriteError::Failed)?;
+        let file = writer.into_inner().map_err(|err| WriteError::Failed(err.into_error()))?;
+        file.sync_all().map_err(WriteError::Failed)?;
+        fs::rename(&temp_path, path).map_err(WriteError::Failed)
+    })();
+    if result.is_err() {
+        let _ = fs::remove_file(&temp_path);
+    }
+    result?;
+    sync_parent_dir(path).map_err(WriteError::Failed)
+}
+
+/// Make the rename durable by syncing the directory entry
+#[cfg(unix)]
+fn sync_parent_dir(path: &Path) -> Result<(), std::io::Error> {
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    File::open(dir)?.sync_all()
+}
+
+#[cfg(not(unix))]
+fn sync_parent_dir(_path: &Path) -> Result<(), std::io::Error> {
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use ndarray::{Array, Ix2};
+
+    use super::{read_sane_file, read_sane_file_dyn, write_sane_arrays_file_dyn, write_sane_file};
+    use crate::Sane;
+
+    #[test]
+    fn roundtrip_file() {
+        let dir = std::env::temp_dir().join(format!("sane-array-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("roundtrip.sane");
+        let arr = ndarray::array![[1, 2, 3], [4, 5, 6]];
+        write_sane_file(&path, &arr).unwrap();
+        let parsed: Array<i32, Ix2> = read_sane_file(&path).unwrap();
+        assert_eq!(parsed, arr);
+
+        // Overwriting replaces the file and leaves no temporary files behind
+        let arrs = vec![Sane::ArrayU8(ndarray::array![1, 2].into_dyn())];
+        write_sane_arrays_file_dyn(&path, &arrs).unwrap();
+        assert_eq!(read_sane_file_dyn(&path).unwrap(), arrs[0]);
+        let entries: Vec<_> = std::fs::read_dir(&dir).unwrap().collect();
+        assert_eq!(entries.len(), 1);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
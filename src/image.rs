@@ -0,0 +1,345 @@
+//! Reading and writing arrays as images
+//!
+//! Arrays of shape `[H, W]` are grayscale images and arrays of shape `[H, W, C]` have `C`
+//! channels: 1 for grayscale, 2 for grayscale with alpha, 3 for RGB and 4 for RGBA. `u8` and
+//! `u16` arrays are written as they are, while floating point arrays are normalized to 8-bit
+//! samples according to [`ImageOptions::range`].
+//!
+//! Binary PGM and PPM files (`P5` and `P6`) need no extra dependencies. PNG files are supported
+//! with the `image` feature, which also adds conversion to and from [`::image::DynamicImage`].
+use std::io::{Read, Write};
+
+use ndarray::{ArrayD, IxDyn};
+
+use crate::data::{DataType, Sane, SaneView};
+
+/// How arrays are converted to images
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct ImageOptions {
+    /// The values of floating point arrays that are mapped to black and white, or the smallest
+    /// and largest finite values of the array if `None`
+    ///
+    /// Values outside the range are clamped and NaN is mapped to black.
+    pub range: Option<(f64, f64)>,
+}
+
+#[derive(Debug)]
+pub enum ImageError {
+    Io(std::io::Error),
+    NotPnm,
+    InvalidHeader(String),
+    UnsupportedShape(Vec<usize>),
+    UnsupportedDataType(DataType),
+    #[cfg(feature = "image")]
+    Image(::image::ImageError),
+}
+
+impl std::fmt::Display for ImageError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        use ImageError::*;
+        match self {
+            Io(err) => write!(f, "I/O error: {}", err),
+            NotPnm => write!(f, "Not a binary PGM or PPM file"),
+            InvalidHeader(reason) => write!(f, "Invalid PGM or PPM header: {}", reason),
+            UnsupportedShape(shape) => write!(f, "Arrays of shape {:?} cannot be stored as images", shape),
+            UnsupportedDataType(data_type) => write!(f, "Arrays of data type {} cannot be stored as images", data_type),
+            #[cfg(feature = "image")]
+            Image(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for ImageError {}
+
+enum SampleData {
+    U8(Vec<u8>),
+    U16(Vec<u16>),
+}
+
+/// The samples of an image in row-major order
+struct Samples {
+    height: usize,
+    width: usize,
+    channels: usize,
+    data: SampleData,
+}
+
+/// Map floating point values onto 8-bit samples
+fn normalize(values: impl Iterator<Item = f64> + Clone, range: Option<(f64, f64)>) -> Vec<u8> {
+    let (low, high) = range.unwrap_or_else(|| {
+        values.clone().filter(|value| value.is_finite())
+            .fold((f64::INFINITY, f64::NEG_INFINITY), |(low, high), value| (low.min(value), high.max(value)))
+    });
+    let scale = if high > low { 255.0 / (high - low) } else { 0.0 };
+    // NaN is mapped to 0 by the saturating cast
+    values.map(|value| ((value - low) * scale).round().clamp(0.0, 255.0) as u8).collect()
+}
+
+fn samples(sane: &SaneView<'_>, options: &ImageOptions) -> Result<Samples, ImageError> {
+    let (height, width, channels) = match *sane.shape() {
+        [height, width] => (height, width, 1),
+        [height, width, channels @ 1..=4] => (height, width, channels),
+        _ => return Err(ImageError::UnsupportedShape(sane.shape().to_vec())),
+    };
+    let data = match sane {
+        SaneView::ArrayU8(array) => SampleData::U8(array.iter().copied().collect()),
+        SaneView::ArrayU16(array) => SampleData::U16(array.iter().copied().collect()),
+        SaneView::ArrayF32(array) => SampleData::U8(normalize(array.iter().map(|&value| f64::from(value)), options.range)),
+        SaneView::ArrayF64(array) => SampleData::U8(normalize(array.iter().copied(), options.range)),
+        #[cfg(feature = "half")]
+        SaneView::ArrayF16(array) => SampleData::U8(normalize(array.iter().map(|value| value.to_f64()), options.range)),
+        #[cfg(feature = "half")]
+        SaneView::ArrayBF16(array) => SampleData::U8(normalize(array.iter().map(|value| value.to_f64()), options.range)),
+        _ => return Err(ImageError::UnsupportedDataType(sane.data_type())),
+    };
+    Ok(Samples { height, width, channels, data })
+}
+
+/// The shape of an array holding an image, without a channel axis for grayscale images
+fn array_shape(height: usize, width: usize, channels: usize) -> IxDyn {
+    match channels {
+        1 => IxDyn(&[height, width]),
+        _ => IxDyn(&[height, width, channels]),
+    }
+}
+
+/// Write a grayscale array as a binary PGM file, or an RGB array as a binary PPM file
+///
+/// `u16` arrays are written with a maximum value of 65535 and all other arrays with a maximum
+/// value of 255.
+pub fn write_pnm<'a, W: Write, S: Into<SaneView<'a>>>(writer: &mut W, sane: S, options: &ImageOptions) -> Result<(), ImageError> {
+    let sane = sane.into();
+    let samples = samples(&sane, options)?;
+    let magic = match samples.channels {
+        1 => "P5",
+        3 => "P6",
+        _ => return Err(ImageError::UnsupportedShape(sane.shape().to_vec())),
+    };
+    let max_value = match samples.data {
+        SampleData::U8(_) => 255,
+        SampleData::U16(_) => 65535,
+    };
+    write!(writer, "{}\n{} {}\n{}\n", magic, samples.width, samples.height, max_value).map_err(ImageError::Io)?;
+    match samples.data {
+        SampleData::U8(data) => writer.write_all(&data),
+        SampleData::U16(data) => {
+            let bytes: Vec<u8> = data.iter().flat_map(|sample| sample.to_be_bytes()).collect();
+            writer.write_all(&bytes)
+        }
+    }.map_err(ImageError::Io)
+}
+
+/// Read the next number of a PGM or PPM header, skipping whitespace and comments, and consume the
+/// single whitespace character after it
+fn header_number<R: Read>(reader: &mut R) -> Result<usize, ImageError> {
+    let mut next_byte = || {
+        let mut byte = [0u8];
+        reader.read_exact(&mut byte).map_err(ImageError::Io)?;
+        Ok::<_, ImageError>(byte[0])
+    };
+    let mut byte = next_byte()?;
+    loop {
+        match byte {
+            b'#' => while byte != b'\n' && byte != b'\r' {
+                byte = next_byte()?;
+            },
+            byte if byte.is_ascii_whitespace() => {}
+            _ => break,
+        }
+        byte = next_byte()?;
+    }
+    let mut number: usize = 0;
+    while byte.is_ascii_digit() {
+        number = number.checked_mul(10)
+            .and_then(|number| number.checked_add(usize::from(byte - b'0')))
+            .ok_or_else(|| ImageError::InvalidHeader("number too large".to_string()))?;
+        byte = next_byte()?;
+    }
+    if !byte.is_ascii_whitespace() {
+        return Err(ImageError::InvalidHeader(format!("unexpected character {:?}", char::from(byte))));
+    }
+    Ok(number)
+}
+
+/// Read a binary PGM or PPM file into a `u8` array, or a `u16` array if its maximum value is
+/// larger than 255
+///
+/// PGM files are read as arrays of shape `[H, W]` and PPM files as arrays of shape `[H, W, 3]`.
+/// Samples are not rescaled to the maximum value. The header is read one byte at a time, so the
+/// reader should be buffered.
+pub fn read_pnm<R: Read>(reader: &mut R) -> Result<Sane, ImageError> {
+    let mut magic = [0u8; 2];
+    reader.read_exact(&mut magic).map_err(ImageError::Io)?;
+    let channels = match &magic {
+        b"P5" => 1,
+        b"P6" => 3,
+        _ => return Err(ImageError::NotPnm),
+    };
+    let width = header_number(reader)?;
+    let height = header_number(reader)?;
+    let max_value = header_number(reader)?;
+    if !(1..=65535).contains(&max_value) {
+        return Err(ImageError::InvalidHeader(format!("invalid maximum value {}", max_value)));
+    }
+    let sample_size = if max_value > 255 { 2 } else { 1 };
+    let byte_length = [height, width, channels, sample_size].iter()
+        .try_fold(1usize, |len, &dim| len.checked_mul(dim))
+        .ok_or_else(|| ImageError::InvalidHeader("image too large".to_string()))?;
+    let mut data = vec![0u8; byte_length];
+    reader.read_exact(&mut data).map_err(ImageError::Io)?;
+    let shape = array_shape(height, width, channels);
+    let sane = if sample_size == 1 {
+        Sane::from(ArrayD::from_shape_vec(shape, data).expect("data matches the shape"))
+    } else {
+        let data = data.chunks_exact(2).map(|bytes| u16::from_be_bytes([bytes[0], bytes[1]])).collect();
+        Sane::from(ArrayD::<u16>::from_shape_vec(shape, data).expect("data matches the shape"))
+    };
+    Ok(sane)
+}
+
+/// Convert an array to an image
+#[cfg(feature = "image")]
+pub fn to_dynamic_image<'a, S: Into<SaneView<'a>>>(sane: S, options: &ImageOptions) -> Result<::image::DynamicImage, ImageError> {
+    use ::image::{DynamicImage, ImageBuffer};
+
+    let sane = sane.into();
+    let samples = samples(&sane, options)?;
+    let unsupported_shape = || ImageError::UnsupportedShape(sane.shape().to_vec());
+    let width = u32::try_from(samples.width).map_err(|_| unsupported_shape())?;
+    let height = u32::try_from(samples.height).map_err(|_| unsupported_shape())?;
+    macro_rules! image {
+        ($variant:ident, $data:expr) => {
+            DynamicImage::$variant(ImageBuffer::from_raw(width, height, $data).expect("samples match the shape"))
+        }
+    }
+    Ok(match (samples.data, samples.channels) {
+        (SampleData::U8(data), 1) => image!(ImageLuma8, data),
+        (SampleData::U8(data), 2) => image!(ImageLumaA8, data),
+        (SampleData::U8(data), 3) => image!(ImageRgb8, data),
+        (SampleData::U8(data), _) => image!(ImageRgba8, data),
+        (SampleData::U16(data), 1) => image!(ImageLuma16, data),
+        (SampleData::U16(data), 2) => image!(ImageLumaA16, data),
+        (SampleData::U16(data), 3) => image!(ImageRgb16, data),
+        (SampleData::U16(data), _) => image!(ImageRgba16, data),
+    })
+}
+
+/// Convert an image to a `u8`, `u16` or `f32` array, depending on its sample type
+///
+/// Grayscale images are converted to arrays of shape `[H, W]` and all other images to arrays of
+/// shape `[H, W, C]`. Images with other sample types are converted to RGBA with 8-bit samples.
+#[cfg(feature = "image")]
+pub fn from_dynamic_image(image: ::image::DynamicImage) -> Sane {
+    use ::image::DynamicImage;
+
+    let height = image.height() as usize;
+    let width = image.width() as usize;
+    let channels = usize::from(image.color().channel_count());
+    let shape = array_shape(height, width, channels);
+    fn array<A>(shape: IxDyn, data: Vec<A>) -> ArrayD<A> {
+        ArrayD::from_shape_vec(shape, data).expect("samples match the shape")
+    }
+    match image {
+        DynamicImage::ImageLuma8(image) => Sane::from(array(shape, image.into_raw())),
+        DynamicImage::ImageLumaA8(image) => Sane::from(array(shape, image.into_raw())),
+        DynamicImage::ImageRgb8(image) => Sane::from(array(shape, image.into_raw())),
+        DynamicImage::ImageRgba8(image) => Sane::from(array(shape, image.into_raw())),
+        DynamicImage::ImageLuma16(image) => Sane::from(array(shape, image.into_raw())),
+        DynamicImage::ImageLumaA16(image) => Sane::from(array(shape, image.into_raw())),
+        DynamicImage::ImageRgb16(image) => Sane::from(array(shape, image.into_raw())),
+        DynamicImage::ImageRgba16(image) => Sane::from(array(shape, image.into_raw())),
+        DynamicImage::ImageRgb32F(image) => Sane::from(array(shape, image.into_raw())),
+        DynamicImage::ImageRgba32F(image) => Sane::from(array(shape, image.into_raw())),
+        image => Sane::from(array(array_shape(height, width, 4), image.into_rgba8().into_raw())),
+    }
+}
+
+/// Write an array as a PNG file
+#[cfg(feature = "image")]
+pub fn write_png<'a, W: Write, S: Into<SaneView<'a>>>(writer: &mut W, sane: S, options: &ImageOptions) -> Result<(), ImageError> {
+    use ::image::ImageEncoder;
+
+    let image = to_dynamic_image(sane, options)?;
+    ::image::codecs::png::PngEncoder::new(writer)
+        .write_image(image.as_bytes(), image.width(), image.height(), image.color().into())
+        .map_err(ImageError::Image)
+}
+
+/// Read a PNG file into an array, as [`from_dynamic_image`] does
+#[cfg(feature = "image")]
+pub fn read_png<R: std::io::BufRead + std::io::Seek>(reader: R) -> Result<Sane, ImageError> {
+    let image = ::image::ImageReader::with_format(reader, ::image::ImageFormat::Png)
+        .decode()
+        .map_err(ImageError::Image)?;
+    Ok(from_dynamic_image(image))
+}
+
+#[cfg(test)]
+mod tests {
+    use ndarray::{array, Array2, Array3};
+
+    use super::{read_pnm, write_pnm, ImageError, ImageOptions};
+    use crate::Sane;
+
+    #[test]
+    fn roundtrip_pnm() {
+        let gray = array![[0u8, 128, 255], [1, 2, 3]];
+        let mut buffer = vec![];
+        write_pnm(&mut buffer, &Sane::from(gray.clone()), &ImageOptions::default()).unwrap();
+        assert!(buffer.starts_with(b"P5\n3 2\n255\n"));
+        let read: Array2<u8> = read_pnm(&mut buffer.as_slice()).unwrap().try_into().unwrap();
+        assert_eq!(read, gray);
+
+        let rgb = Array3::from_shape_fn((2, 2, 3), |(i, j, k)| (i * 1000 + j * 100 + k) as u16);
+        let mut buffer = vec![];
+        write_pnm(&mut buffer, &Sane::from(rgb.clone()), &ImageOptions::default()).unwrap();
+        assert!(buffer.starts_with(b"P6\n2 2\n65535\n"));
+        let read: Array3<u16> = read_pnm(&mut buffer.as_slice()).unwrap().try_into().unwrap();
+        assert_eq!(read, rgb);
+    }
+
+    #[test]
+    fn header_comments() {
+        let file = b"P5 # a comment\n2 # width\n1\n# max\n15\n\x01\x0f";
+        let read: Array2<u8> = read_pnm(&mut file.as_slice()).unwrap().try_into().unwrap();
+        assert_eq!(read, array![[1, 15]]);
+        assert!(matches!(read_pnm(&mut b"P3\n1 1\n255\n0".as_slice()), Err(ImageError::NotPnm)));
+    }
+
+    #[test]
+    fn normalize_floats() {
+        let values = Sane::from(array![[-1.0f32, 0.0], [1.0, f32::NAN]]);
+        let mut buffer = vec![];
+        write_pnm(&mut buffer, &values, &ImageOptions::default()).unwrap();
+        assert!(buffer.ends_with(&[0, 128, 255, 0]));
+        let mut buffer = vec![];
+        write_pnm(&mut buffer, &values, &ImageOptions { range: Some((0.0, 0.5)) }).unwrap();
+        assert!(buffer.ends_with(&[0, 0, 255, 0]));
+    }
+
+    #[test]
+    fn unsupported_arrays() {
+        let rgba = Sane::from(Array3::<u8>::zeros((1, 1, 4)));
+        assert!(matches!(write_pnm(&mut vec![], &rgba, &ImageOptions::default()), Err(ImageError::UnsupportedShape(_))));
+        let signed = Sane::from(Array2::<i32>::zeros((1, 1)));
+        assert!(matches!(write_pnm(&mut vec![], &signed, &ImageOptions::default()), Err(ImageError::UnsupportedDataType(_))));
+    }
+
+    #[cfg(feature = "image")]
+    #[test]
+    fn roundtrip_png() {
+        use super::{read_png, write_png};
+
+        let rgba = Array3::from_shape_fn((3, 2, 4), |(i, j, k)| (i * 40 + j * 10 + k) as u8);
+        let mut buffer = vec![];
+        write_png(&mut buffer, &Sane::from(rgba.clone()), &ImageOptions::default()).unwrap();
+        let read: Array3<u8> = read_png(std::io::Cursor::new(&buffer)).unwrap().try_into().unwrap();
+        assert_eq!(read, rgba);
+
+        let gray = Array2::from_shape_fn((2, 3), |(i, j)| (i * 30000 + j) as u16);
+        let mut buffer = vec![];
+        write_png(&mut buffer, &Sane::from(gray.clone()), &ImageOptions::default()).unwrap();
+        let read: Array2<u16> = read_png(std::io::Cursor::new(&buffer)).unwrap().try_into().unwrap();
+        assert_eq!(read, gray);
+    }
+}
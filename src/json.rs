@@ -0,0 +1,446 @@
+//! Conversion between [`Sane`] arrays and JSON values holding nested lists
+//!
+//! An array of shape `[2, 3]` is represented as a list of two lists of three numbers each, and a
+//! zero-dimensional array as a single number. Booleans are JSON booleans and complex numbers are
+//! `[re, im]` pairs. Optionally, the array is written as an object that also records the data type
+//! and shape, as in `{"dtype": "f32", "shape": [2, 2], "data": [1.0, 2.0, 3.0, 4.0]}`, with the
+//! elements flattened in row-major order. This is the same representation as that of the `serde`
+//! module, and keeps the shape of arrays with empty axes.
+use serde_json::{Map, Number, Value};
+use ndarray::{ArrayD, ArrayViewD, Dimension, ErrorKind, IxDyn, ShapeError};
+
+use crate::data::{DataType, Sane, SaneView};
+use crate::element::{non_finite_name, parse_non_finite, SaneElement};
+
+/// How NaN and infinite floating point values, which JSON numbers cannot represent, are written
+///
+/// When reading, `null` is read as NaN, and the strings written by [`NonFinite::String`] are
+/// read as the corresponding values, whatever the policy.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum NonFinite {
+    /// Fail with [`JsonError::NonFinite`]
+    #[default]
+    Error,
+    /// Write `null`, which loses the distinction between NaN and the infinities
+    Null,
+    /// Write the strings `"NaN"`, `"Infinity"` and `"-Infinity"`
+    String,
+}
+
+/// How arrays are converted to JSON values
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct JsonOptions {
+    /// Write an object with `dtype`, `shape` and flat `data` fields instead of nested lists
+    pub wrap: bool,
+    pub non_finite: NonFinite,
+}
+
+#[derive(Debug)]
+pub enum JsonError {
+    /// A NaN or infinite value, with its index, while NaN and infinities are not allowed
+    NonFinite(Vec<usize>),
+    /// An element at the given index that is not a valid value of the data type
+    InvalidElement(Vec<usize>),
+    /// A list at the given index whose length differs from the other lists at its level
+    Ragged(Vec<usize>),
+    /// The data type is neither given nor recorded in the value
+    MissingDataType,
+    /// The data type recorded in the value differs from the given one
+    WrongDataType(DataType),
+    /// The wrapper object is missing a field or has an invalid one
+    InvalidWrapper(String),
+    ShapeError(ShapeError),
+}
+
+impl std::fmt::Display for JsonError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        use JsonError::*;
+        match self {
+            NonFinite(index) => write!(f, "Non-finite value at index {:?}", index),
+            InvalidElement(index) => write!(f, "Invalid element at index {:?}", index),
+            Ragged(index) => write!(f, "List at index {:?} does not match the shape of the array", index),
+            MissingDataType => write!(f, "Data type of the array is not known"),
+            WrongDataType(data_type) => write!(f, "unexpected data type {}", data_type),
+            InvalidWrapper(reason) => write!(f, "Invalid array object: {}", reason),
+            ShapeError(err) => write!(f, "Shape error: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for JsonError {}
+
+/// Conversion of single elements from and to JSON values
+trait JsonElement: SaneElement {
+    /// Convert to a JSON value, or `None` for non-finite values that the policy does not allow
+    fn to_json(&self, non_finite: NonFinite) -> Option<Value>;
+
+    fn from_json(value: &Value) -> Option<Self>;
+
+    /// Whether `value` is a single element rather than a list of elements
+    fn is_element(value: &Value) -> bool {
+        !value.is_array()
+    }
+}
+
+macro_rules! integer_element {
+    ($($t:ty),*) => {
+        $(impl JsonElement for $t {
+            fn to_json(&self, _non_finite: NonFinite) -> Option<Value> {
+                Some(Value::from(*self))
+            }
+
+            fn from_json(value: &Value) -> Option<Self> {
+                match value {
+                    Value::Number(number) => number.as_i64().and_then(|n| n.try_into().ok())
+                        .or_else(|| number.as_u64().and_then(|n| n.try_into().ok())),
+                    _ => None,
+                }
+            }
+        })*
+    }
+}
+
+integer_element!(i32, u32, i64, u64, i8, u8, i16, u16);
+
+fn float_to_json(value: f64, non_finite: NonFinite) -> Option<Value> {
+    if let Some(number) = Number::from_f64(value) {
+        return Some(Value::Number(number));
+    }
+    match non_finite {
+        NonFinite::Error => None,
+        NonFinite::Null => Some(Value::Null),
+        NonFinite::String => non_finite_name(value).map(Value::from),
+    }
+}
+
+fn float_from_json(value: &Value) -> Option<f64> {
+    match value {
+        Value::Number(number) => number.as_f64(),
+        Value::Null => Some(f64::NAN),
+        Value::String(text) => parse_non_finite(text),
+        _ => None,
+    }
+}
+
+// Floating point types, converted through `f64`
+macro_rules! float_element {
+    ($t:ty, $to_f64:expr, $from_f64:expr) => {
+        impl JsonElement for $t {
+            fn to_json(&self, non_finite: NonFinite) -> Option<Value> {
+                float_to_json($to_f64(*self), non_finite)
+            }
+
+            fn from_json(value: &Value) -> Option<Self> {
+                float_from_json(value).map($from_f64)
+            }
+        }
+    }
+}
+
+float_element!(f32, f64::from, |value| value as f32);
+float_element!(f64, |value| value, |value| value);
+#[cfg(feature = "half")]
+float_element!(half::f16, half::f16::to_f64, half::f16::from_f64);
+#[cfg(feature = "half")]
+float_element!(half::bf16, half::bf16::to_f64, half::bf16::from_f64);
+
+impl JsonElement for bool {
+    fn to_json(&self, _non_finite: NonFinite) -> Option<Value> {
+        Some(Value::Bool(*self))
+    }
+
+    fn from_json(value: &Value) -> Option<Self> {
+        value.as_bool()
+    }
+}
+
+#[cfg(feature = "num-complex")]
+macro_rules! complex_element {
+    ($t:ty, $to_f64:expr, $from_f64:expr) => {
+        impl JsonElement for num_complex::Complex<$t> {
+            fn to_json(&self, non_finite: NonFinite) -> Option<Value> {
+                let re = float_to_json($to_f64(self.re), non_finite)?;
+                let im = float_to_json($to_f64(self.im), non_finite)?;
+                Some(Value::Array(vec![re, im]))
+            }
+
+            fn from_json(value: &Value) -> Option<Self> {
+                match value.as_array()?.as_slice() {
+                    [re, im] => Some(num_complex::Complex::new(
+                        $from_f64(float_from_json(re)?),
+                        $from_f64(float_from_json(im)?),
+                    )),
+                    _ => None,
+                }
+            }
+
+            /// A pair of numbers, as opposed to a list of pairs
+            fn is_element(value: &Value) -> bool {
+                value.as_array().is_some_and(|items| items.first().is_some_and(|item| !item.is_array()))
+            }
+        }
+    }
+}
+
+#[cfg(feature = "num-complex")]
+complex_element!(f32, f64::from, |value| value as f32);
+#[cfg(feature = "num-complex")]
+complex_element!(f64, |value| value, |value| value);
+
+fn nested_lists<A: JsonElement>(array: &ArrayViewD<'_, A>, index: &mut Vec<usize>, non_finite: NonFinite) -> Result<Value, JsonError> {
+    if index.len() == array.ndim() {
+        return array[index.as_slice()].to_json(non_finite).ok_or_else(|| JsonError::NonFinite(index.clone()));
+    }
+    let len = array.shape()[index.len()];
+    let mut items = Vec::with_capacity(len);
+    for i in 0..len {
+        index.push(i);
+        items.push(nested_lists(array, index, non_finite)?);
+        index.pop();
+    }
+    Ok(Value::Array(items))
+}
+
+/// The elements of an array as a flat list in row-major order
+fn flat_list<A: JsonElement>(array: &ArrayViewD<'_, A>, non_finite: NonFinite) -> Result<Value, JsonError> {
+    array.indexed_iter()
+        .map(|(index, element)| element.to_json(non_finite).ok_or_else(|| JsonError::NonFinite(index.slice().to_vec())))
+        .collect::<Result<Vec<_>, _>>()
+        .map(Value::Array)
+}
+
+/// The index of the element at position `flat` of an array of the given shape in row-major order
+fn unravel(mut flat: usize, shape: &[usize]) -> Vec<usize> {
+    let mut index = vec![0; shape.len()];
+    for (i, &dim) in shape.iter().enumerate().rev() {
+        index[i] = flat % dim;
+        flat /= dim;
+    }
+    index
+}
+
+fn from_flat_list<A: JsonElement>(value: &Value, shape: Vec<usize>) -> Result<Sane, JsonError> {
+    // The shape comes from the input, so it is only trusted once the elements match it
+    let len = shape.iter()
+        .try_fold(1usize, |len, &dim| len.checked_mul(dim))
+        .ok_or_else(|| JsonError::ShapeError(ShapeError::from_kind(ErrorKind::Overflow)))?;
+    let items = value.as_array()
+        .filter(|items| items.len() == len)
+        .ok_or_else(|| JsonError::Ragged(vec![]))?;
+    let elements = items.iter()
+        .enumerate()
+        .map(|(i, item)| A::from_json(item).ok_or_else(|| JsonError::InvalidElement(unravel(i, &shape))))
+        .collect::<Result<Vec<A>, _>>()?;
+    let array = ArrayD::from_shape_vec(IxDyn(&shape), elements).map_err(JsonError::ShapeError)?;
+    Ok(Sane::from(array))
+}
+
+/// The shape of nested lists, following the first item of each list
+fn infer_shape<A: JsonElement>(mut value: &Value) -> Vec<usize> {
+    let mut shape = vec![];
+    while let (false, Some(items)) = (A::is_element(value), value.as_array()) {
+        shape.push(items.len());
+        match items.first() {
+            Some(first) => value = first,
+            None => break,
+        }
+    }
+    shape
+}
+
+/// Collect the elements of nested lists in row-major order, checking that they have the given
+/// shape
+fn collect_elements<A: JsonElement>(value: &Value, shape: &[usize], index: &mut Vec<usize>, elements: &mut Vec<A>) -> Result<(), JsonError> {
+    let Some((&len, inner_shape)) = shape.split_first() else {
+        let element = A::from_json(value).ok_or_else(|| JsonError::InvalidElement(index.clone()))?;
+        elements.push(element);
+        return Ok(());
+    };
+    let items = value.as_array()
+        .filter(|items| items.len() == len)
+        .ok_or_else(|| JsonError::Ragged(index.clone()))?;
+    for (i, item) in items.iter().enumerate() {
+        index.push(i);
+        collect_elements(item, inner_shape, index, elements)?;
+        index.pop();
+    }
+    Ok(())
+}
+
+fn from_nested_lists<A: JsonElement>(value: &Value) -> Result<Sane, JsonError> {
+    let shape = infer_shape::<A>(value);
+    let mut elements: Vec<A> = vec![];
+    collect_elements(value, &shape, &mut vec![], &mut elements)?;
+    let array = ArrayD::from_shape_vec(IxDyn(&shape), elements).map_err(JsonError::ShapeError)?;
+    Ok(Sane::from(array))
+}
+
+/// The data type, shape and data of a wrapped array
+fn unwrap_object(object: &Map<String, Value>) -> Result<(DataType, Vec<usize>, &Value), JsonError> {
+    let field = |name: &str| object.get(name).ok_or_else(|| JsonError::InvalidWrapper(format!("missing field {}", name)));
+    let data_type = field("dtype")?.as_str()
+        .and_then(|name| name.parse().ok())
+        .ok_or_else(|| JsonError::InvalidWrapper("invalid dtype".to_string()))?;
+    let shape = field("shape")?.as_array()
+        .and_then(|dims| dims.iter().map(|dim| dim.as_u64().and_then(|dim| usize::try_from(dim).ok())).collect())
+        .ok_or_else(|| JsonError::InvalidWrapper("invalid shape".to_string()))?;
+    Ok((data_type, shape, field("data")?))
+}
+
+impl<'a> SaneView<'a> {
+    /// Convert the array to nested lists, or to an object with its data type, shape and flat data
+    pub fn to_json_value(&self, options: &JsonOptions) -> Result<Value, JsonError> {
+        if !options.wrap {
+            return crate::sane_dispatch!(SaneView: self, array => nested_lists(array, &mut vec![], options.non_finite));
+        }
+        let data = crate::sane_dispatch!(SaneView: self, array => flat_list(array, options.non_finite))?;
+        let mut object = Map::new();
+        object.insert("dtype".to_string(), Value::from(self.data_type().name()));
+        object.insert("shape".to_string(), Value::from(self.shape()));
+        object.insert("data".to_string(), data);
+        Ok(Value::Object(object))
+    }
+}
+
+impl Sane {
+    /// Convert the array to nested lists, or to an object with its data type, shape and flat data
+    pub fn to_json_value(&self, options: &JsonOptions) -> Result<Value, JsonError> {
+        self.view().to_json_value(options)
+    }
+
+    /// Convert nested lists, or an object with `dtype`, `shape` and flat `data` fields as written
+    /// with [`JsonOptions::wrap`] or by the `serde` module, to an array
+    ///
+    /// The data type must be given for nested lists, and must match the recorded one for objects
+    /// if given. The shape of nested lists is that of their first items, and every list must
+    /// match it.
+    pub fn from_json_value(value: &Value, data_type: Option<DataType>) -> Result<Sane, JsonError> {
+        let (data_type, shape, data) = match value {
+            Value::Object(object) => {
+                let (recorded, shape, data) = unwrap_object(object)?;
+                if data_type.is_some_and(|data_type| data_type != recorded) {
+                    return Err(JsonError::WrongDataType(recorded));
+                }
+                (recorded, Some(shape), data)
+            }
+            _ => (data_type.ok_or(JsonError::MissingDataType)?, None, value),
+        };
+        crate::sane_dispatch!(
+            DataType: data_type,
+            A => match shape {
+                Some(shape) => from_flat_list::<A>(data, shape),
+                None => from_nested_lists::<A>(data),
+            },
+            _ => Err(JsonError::InvalidWrapper(format!("data type {} is not enabled in this build", data_type)))
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ndarray::{array, ArrayD, IxDyn};
+    use serde_json::json;
+
+    use super::{JsonError, JsonOptions, NonFinite};
+    use crate::{DataType, Sane};
+
+    #[test]
+    fn nested_lists() {
+        let sane = Sane::from(array![[1i32, 2, 3], [4, 5, 6]]);
+        let value = sane.to_json_value(&JsonOptions::default()).unwrap();
+        assert_eq!(value, json!([[1, 2, 3], [4, 5, 6]]));
+        assert_eq!(Sane::from_json_value(&value, Some(DataType::I32)).unwrap(), sane);
+        assert!(matches!(Sane::from_json_value(&value, None), Err(JsonError::MissingDataType)));
+
+        let scalar = Sane::from(ndarray::arr0(true).into_dyn());
+        assert_eq!(scalar.to_json_value(&JsonOptions::default()).unwrap(), json!(true));
+        assert_eq!(Sane::from_json_value(&json!(true), Some(DataType::Bool)).unwrap(), scalar);
+    }
+
+    #[test]
+    fn wrapped() {
+        let empty = Sane::from(ArrayD::<f32>::zeros(IxDyn(&[2, 0])));
+        let options = JsonOptions { wrap: true, ..JsonOptions::default() };
+        let value = empty.to_json_value(&options).unwrap();
+        assert_eq!(value, json!({"dtype": "f32", "shape": [2, 0], "data": []}));
+        assert_eq!(Sane::from_json_value(&value, None).unwrap(), empty);
+        assert!(matches!(Sane::from_json_value(&value, Some(DataType::F64)), Err(JsonError::WrongDataType(DataType::F32))));
+
+        let sane = Sane::from(array![[1u8, 2, 3], [4, 5, 6]]);
+        let value = sane.to_json_value(&options).unwrap();
+        assert_eq!(value, json!({"dtype": "u8", "shape": [2, 3], "data": [1, 2, 3, 4, 5, 6]}));
+        assert_eq!(Sane::from_json_value(&value, None).unwrap(), sane);
+
+        let wrong_shape = json!({"dtype": "u8", "shape": [3], "data": [1, 2]});
+        assert!(matches!(Sane::from_json_value(&wrong_shape, None), Err(JsonError::Ragged(_))));
+        let invalid = json!({"dtype": "u8", "shape": [2, 2], "data": [1, 2, 3, -4]});
+        let err = Sane::from_json_value(&invalid, None).unwrap_err();
+        assert!(matches!(err, JsonError::InvalidElement(index) if index == [1, 1]));
+    }
+
+    /// The wrapped form is the human-readable representation of the `serde` module
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_compatible() {
+        let sane = Sane::from(array![[0.5f32, f32::NAN], [f32::INFINITY, f32::NEG_INFINITY]]);
+        let options = JsonOptions { wrap: true, non_finite: NonFinite::String };
+        let value = sane.to_json_value(&options).unwrap();
+        assert_eq!(serde_json::to_value(&sane).unwrap(), value);
+
+        let from_serde: ndarray::Array2<f32> = Sane::from_json_value(&serde_json::to_value(&sane).unwrap(), None).unwrap().try_into().unwrap();
+        let from_json: ndarray::Array2<f32> = serde_json::from_value::<Sane>(value).unwrap().try_into().unwrap();
+        for read in [from_serde, from_json] {
+            assert_eq!(read[[0, 0]], 0.5);
+            assert!(read[[0, 1]].is_nan());
+            assert_eq!(read[[1, 0]], f32::INFINITY);
+            assert_eq!(read[[1, 1]], f32::NEG_INFINITY);
+        }
+    }
+
+    #[test]
+    fn oversized_shape() {
+        let overflow = json!({"dtype": "u8", "shape": [4294967296u64, 4294967296u64, 16], "data": []});
+        assert!(matches!(Sane::from_json_value(&overflow, None), Err(JsonError::ShapeError(_))));
+        let huge = json!({"dtype": "f64", "shape": [1125899906842624u64], "data": []});
+        assert!(matches!(Sane::from_json_value(&huge, None), Err(JsonError::Ragged(_))));
+    }
+
+    #[test]
+    fn non_finite() {
+        let sane = Sane::from(array![1.0f64, f64::NAN, f64::NEG_INFINITY]);
+        let err = sane.to_json_value(&JsonOptions::default()).unwrap_err();
+        assert!(matches!(err, JsonError::NonFinite(index) if index == [1]));
+
+        let options = JsonOptions { non_finite: NonFinite::Null, ..JsonOptions::default() };
+        assert_eq!(sane.to_json_value(&options).unwrap(), json!([1.0, null, null]));
+
+        let options = JsonOptions { non_finite: NonFinite::String, ..JsonOptions::default() };
+        let value = sane.to_json_value(&options).unwrap();
+        assert_eq!(value, json!([1.0, "NaN", "-Infinity"]));
+        let read: ndarray::Array1<f64> = Sane::from_json_value(&value, Some(DataType::F64)).unwrap().try_into().unwrap();
+        assert!(read[1].is_nan());
+        assert_eq!(read[2], f64::NEG_INFINITY);
+    }
+
+    #[test]
+    fn invalid_input() {
+        let ragged = json!([[1, 2], [3]]);
+        let err = Sane::from_json_value(&ragged, Some(DataType::U8)).unwrap_err();
+        assert!(matches!(err, JsonError::Ragged(index) if index == [1]));
+        let too_deep = json!([[1, 2], [3, [4]]]);
+        let err = Sane::from_json_value(&too_deep, Some(DataType::U8)).unwrap_err();
+        assert!(matches!(err, JsonError::InvalidElement(index) if index == [1, 1]));
+        let overflow = json!([1, 256]);
+        assert!(matches!(Sane::from_json_value(&overflow, Some(DataType::U8)), Err(JsonError::InvalidElement(_))));
+    }
+
+    #[cfg(feature = "num-complex")]
+    #[test]
+    fn complex() {
+        use num_complex::Complex;
+        let sane = Sane::from(array![[Complex::new(1.0f64, -1.0)], [Complex::new(0.5, 2.0)]]);
+        let value = sane.to_json_value(&JsonOptions::default()).unwrap();
+        assert_eq!(value, json!([[[1.0, -1.0]], [[0.5, 2.0]]]));
+        assert_eq!(Sane::from_json_value(&value, Some(DataType::C128)).unwrap(), sane);
+    }
+}
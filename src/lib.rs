@@ -5,23 +5,60 @@
 pub mod write;
 pub mod read;
 pub mod data;
+pub mod element;
+pub mod visit;
+pub mod npy;
+pub mod raw;
+pub mod cbor;
+pub mod text;
+pub mod image;
+#[cfg(feature = "npz")]
+pub mod npz;
+#[cfg(feature = "safetensors")]
+pub mod safetensors;
+#[cfg(feature = "arrow")]
+pub mod arrow;
+#[cfg(feature = "onnx")]
+pub mod onnx;
+#[cfg(feature = "nalgebra")]
+pub mod nalgebra;
+#[cfg(any(test, feature = "quickcheck"))]
+pub mod arbitrary;
+#[cfg(feature = "proptest")]
+pub mod strategy;
+#[cfg(feature = "serde")]
+pub mod serde;
+#[cfg(feature = "json")]
+pub mod json;
+pub mod file;
 
 #[doc(inline)]
-pub use crate::read::{read_sane, read_sane_dyn, read_sane_arrays, read_sane_arrays_dyn, ReadSane};
+pub use crate::read::{read_sane, read_sane_dyn, read_sane_arrays, read_sane_arrays_dyn};
 #[doc(inline)]
-pub use crate::write::{write_sane, write_sane_io, write_sane_arrays, write_sane_arrays_io, write_sane_arrays_dyn, WriteSane};
+pub use crate::write::{write_sane, write_sane_io, write_sane_arrays, write_sane_arrays_io, write_sane_dyn, write_sane_arrays_dyn, encoded_len, header_len};
 #[doc(inline)]
-pub use crate::data::{SaneData, Sane};
+pub use crate::file::{read_sane_file, read_sane_file_dyn, write_sane_file, write_sane_arrays_file, write_sane_arrays_file_dyn};
+#[doc(inline)]
+pub use crate::data::{DataType, ParseDataTypeError, Sane, SaneView, SaneViewMut};
+#[doc(inline)]
+pub use crate::element::SaneElement;
+#[doc(inline)]
+pub use crate::visit::{SaneVisitor, SaneViewVisitor};
+#[cfg(feature = "half")]
+pub use half;
+#[cfg(feature = "num-complex")]
+pub use num_complex;
 
 
 #[cfg(test)]
+#[allow(clippy::assertions_on_constants)]
 mod tests {
     use ndarray::{Ix2, Array, Ix3};
 
     use crate::data::Sane;
     use crate::write::{write_sane, write_sane_arrays};
     use crate::read::{read_sane, read_sane_dyn, ParseError, read_sane_arrays};
-    use crate::{write_sane_arrays_dyn, read_sane_arrays_dyn};
+    use crate::{write_sane_dyn, write_sane_arrays_dyn, read_sane_arrays_dyn};
     extern crate quickcheck;
     use std::io::Cursor;
 
@@ -45,7 +82,7 @@ mod tests {
         let mut file = Cursor::new(Vec::new());
         write_sane(&mut file, &arr).unwrap();
         file.set_position(0);
-        let arr2 = read_sane(&mut file).unwrap();
+        let arr2: Array<i32, Ix2> = read_sane(&mut file).unwrap();
         assert_eq!(arr, arr2)
     }
 
@@ -85,6 +122,39 @@ mod tests {
             ArrayF64(ndarray::array![[1.0], [2.0], [3.0], [5.0]].into_dyn()),
             ArrayU8(ndarray::array![[1], [2], [3], [5], [250]].into_dyn()),
             ArrayI8(ndarray::array![[1], [-2], [3], [5], [-128]].into_dyn()),
+            ArrayI16(ndarray::array![[1, -300], [-32768, 32767]].into_dyn()),
+            ArrayU16(ndarray::array![1, 300, 65535].into_dyn()),
+            ArrayBool(ndarray::array![[true, false], [false, true]].into_dyn()),
+        ];
+        let mut file = Cursor::new(Vec::new());
+        write_sane_arrays_dyn(&mut file, &arrs).unwrap();
+        file.set_position(0);
+        let parsed: Vec<Sane> = read_sane_arrays_dyn(&mut file).unwrap();
+        assert_eq!(parsed, arrs)
+    }
+
+    #[cfg(feature = "half")]
+    #[test]
+    fn roundtrip_half() {
+        use half::{f16, bf16};
+        let arrs = vec![
+            Sane::ArrayF16(ndarray::array![[1.0, -2.5], [0.125, 65504.0]].mapv(f16::from_f32).into_dyn()),
+            Sane::ArrayBF16(ndarray::array![1.0, -2.5, 1e30].mapv(bf16::from_f32).into_dyn()),
+        ];
+        let mut file = Cursor::new(Vec::new());
+        write_sane_arrays_dyn(&mut file, &arrs).unwrap();
+        file.set_position(0);
+        let parsed: Vec<Sane> = read_sane_arrays_dyn(&mut file).unwrap();
+        assert_eq!(parsed, arrs)
+    }
+
+    #[cfg(feature = "num-complex")]
+    #[test]
+    fn roundtrip_complex() {
+        use num_complex::Complex;
+        let arrs = vec![
+            Sane::ArrayC64(ndarray::array![[Complex::new(1.0, -2.0), Complex::new(0.5, 3.25)]].into_dyn()),
+            Sane::ArrayC128(ndarray::array![Complex::new(-1e100, 2.0), Complex::new(0.0, -0.125)].into_dyn()),
         ];
         let mut file = Cursor::new(Vec::new());
         write_sane_arrays_dyn(&mut file, &arrs).unwrap();
@@ -92,4 +162,143 @@ mod tests {
         let parsed: Vec<Sane> = read_sane_arrays_dyn(&mut file).unwrap();
         assert_eq!(parsed, arrs)
     }
+
+    #[cfg(feature = "num-complex")]
+    #[test]
+    fn complex_is_interleaved() {
+        use num_complex::Complex;
+        let arr = ndarray::array![Complex::new(1.0f32, 2.0), Complex::new(3.0, 4.0)];
+        let mut file = Vec::new();
+        write_sane(&mut file, &arr).unwrap();
+        let data: Vec<u8> = [1.0f32, 2.0, 3.0, 4.0].iter().flat_map(|x| x.to_le_bytes()).collect();
+        assert_eq!(&file[file.len() - data.len()..], &data[..]);
+    }
+
+    #[test]
+    fn typed_conversions() {
+        let arr = ndarray::array![[1.0f64, 2.0, 3.0], [4.0, 5.0, 6.0]];
+        let sane = Sane::from(arr.clone());
+        assert_eq!(sane.shape(), &[2, 3]);
+        assert_eq!(sane.ndim(), 2);
+        assert_eq!(sane.len(), 6);
+        assert_eq!(sane.data_type(), crate::data::DataType::F64);
+        assert_eq!(sane.byte_len(), 48);
+        assert_eq!(sane.as_typed::<f64>(), Some(&arr.clone().into_dyn()));
+        assert_eq!(sane.as_typed::<f32>(), None);
+        let sane = sane.into_typed::<i32>().unwrap_err();
+        let typed: Array<f64, Ix2> = sane.clone().try_into().unwrap();
+        assert_eq!(typed, arr);
+        let wrong_rank: Result<Array<f64, Ix3>, _> = sane.clone().try_into();
+        assert!(matches!(wrong_rank, Err(ParseError::ShapeError(_))));
+        let wrong_type: Result<Array<u8, Ix2>, _> = sane.try_into();
+        assert!(matches!(wrong_type, Err(ParseError::WrongDataType(_))));
+    }
+
+    #[test]
+    fn visit_sane() {
+        use crate::{SaneElement, SaneVisitor};
+        use ndarray::ArrayD;
+
+        /// Reverses the order of the elements along the first axis
+        struct Flip;
+
+        impl SaneVisitor for Flip {
+            type Output = Sane;
+
+            fn visit<A: SaneElement>(self, mut array: ArrayD<A>) -> Sane {
+                array.invert_axis(ndarray::Axis(0));
+                Sane::from(array)
+            }
+        }
+
+        let sane = Sane::from(ndarray::array![[1u16, 2], [3, 4]]);
+        assert_eq!(sane.visit(Flip), Sane::from(ndarray::array![[3u16, 4], [1, 2]]));
+    }
+
+    quickcheck::quickcheck! {
+        fn prop_roundtrip_sane(sane: Sane) -> bool {
+            // Compare encodings rather than values, which may contain NaNs
+            let mut file = Vec::new();
+            write_sane_dyn(&mut file, &sane).unwrap();
+            let parsed = read_sane_dyn(&mut Cursor::new(&file)).unwrap();
+            let mut reencoded = Vec::new();
+            write_sane_dyn(&mut reencoded, &parsed).unwrap();
+            file == reencoded
+        }
+    }
+
+    #[test]
+    fn invalid_bool() {
+        let arr = ndarray::array![true, false, true];
+        let mut file = Vec::new();
+        write_sane(&mut file, &arr).unwrap();
+        let last = file.len() - 1;
+        file[last] = 2;
+        let result = read_sane_dyn(&mut Cursor::new(file));
+        assert!(matches!(result, Err(ParseError::InvalidBool(2))));
+    }
+
+    #[test]
+    fn encoded_len_matches_written() {
+        use Sane::*;
+        let arrs = vec![
+            ArrayI32(ndarray::array![[1,2,3], [4,5,-6]].into_dyn()),
+            ArrayF64(ndarray::array![[1.0], [2.0], [3.0], [5.0]].into_dyn()),
+            ArrayU8(ndarray::arr0(7).into_dyn()),
+        ];
+        let expected: usize = arrs.iter().map(Sane::encoded_len).sum();
+        let mut file = Vec::with_capacity(expected);
+        write_sane_arrays_dyn(&mut file, &arrs).unwrap();
+        assert_eq!(file.len(), expected);
+    }
+
+    #[test]
+    fn roundtrip_non_contiguous() {
+        let arr = ndarray::array![[1,2,3], [4,5,6]];
+        let transposed = arr.t();
+        let mut file = Cursor::new(Vec::new());
+        write_sane(&mut file, &transposed).unwrap();
+        file.set_position(0);
+        let parsed: Array<i32, Ix2> = read_sane(&mut file).unwrap();
+        assert_eq!(parsed, transposed);
+    }
+
+    #[test]
+    fn roundtrip_views() {
+        use crate::SaneView;
+        let ints = ndarray::array![[1, 2, 3], [4, 5, 6]];
+        let floats = vec![0.5f32, 1.5, 2.5, 3.5];
+        let float_view = ndarray::ArrayView::from_shape((2, 2), &floats).unwrap();
+        let views: Vec<SaneView> = vec![ints.view().into(), float_view.into(), ints.t().into()];
+        let mut file = Cursor::new(Vec::new());
+        write_sane_arrays_dyn(&mut file, views.iter().cloned()).unwrap();
+        file.set_position(0);
+        let parsed = read_sane_arrays_dyn(&mut file).unwrap();
+        let owned: Vec<Sane> = views.iter().map(SaneView::to_owned).collect();
+        assert_eq!(parsed, owned);
+    }
+
+    /// A writer that accepts at most three bytes per call
+    struct Trickle(Vec<u8>);
+
+    impl std::io::Write for Trickle {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            let n = buf.len().min(3);
+            self.0.extend_from_slice(&buf[..n]);
+            Ok(n)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn roundtrip_short_writes() {
+        let arr = ndarray::array![[1.5f32, 2.0], [3.0, -4.25]];
+        let mut file = Trickle(Vec::new());
+        write_sane(&mut file, &arr).unwrap();
+        let parsed: Array<f32, Ix2> = read_sane(&mut Cursor::new(file.0)).unwrap();
+        assert_eq!(parsed, arr);
+    }
 }
@@ -0,0 +1,115 @@
+//! Reading and writing [nalgebra](https://nalgebra.org) matrices and vectors
+//!
+//! A matrix with `R` rows and `C` columns is stored as a two-dimensional SANE array of shape
+//! `[R, C]`, so vectors are stored as arrays of shape `[R, 1]`. One-dimensional arrays are read as
+//! column vectors. Elements are reordered between nalgebra's column-major storage and SANE's
+//! row-major layout as needed.
+use std::io::{Read, Write};
+
+use ::nalgebra::allocator::Allocator;
+use ::nalgebra::{DefaultAllocator, Dim, Matrix, OMatrix, RawStorage, Scalar};
+use ndarray::{ArrayView2, ErrorKind, IxDyn, ShapeBuilder, ShapeError};
+
+use crate::element::SaneElement;
+use crate::read::{read_sane, ParseError};
+use crate::write::{write_sane, WriteError};
+
+/// View a matrix as a two-dimensional array without copying its elements
+pub fn matrix_view<T, R, C, S>(matrix: &Matrix<T, R, C, S>) -> ArrayView2<'_, T>
+where
+    R: Dim,
+    C: Dim,
+    S: RawStorage<T, R, C>,
+{
+    let (rows, cols) = matrix.shape();
+    let (row_stride, col_stride) = matrix.strides();
+    let shape = (rows, cols).strides((row_stride, col_stride));
+    // SAFETY: the pointer and strides describe the elements of `matrix`, which stays borrowed for
+    // the lifetime of the view
+    unsafe { ArrayView2::from_shape_ptr(shape, matrix.data.ptr()) }
+}
+
+/// Parse a SANE-encoded array of shape `[R, C]`, or `[R]` for a column vector, into a matrix
+pub fn read_sane_matrix<F, T, R, C>(file: &mut F) -> Result<OMatrix<T, R, C>, ParseError>
+where
+    F: Read,
+    T: SaneElement + Scalar,
+    R: Dim,
+    C: Dim,
+    DefaultAllocator: Allocator<R, C>,
+{
+    let array = read_sane::<F, T, IxDyn>(file)?;
+    let (rows, cols) = match *array.shape() {
+        [rows, cols] => (rows, cols),
+        [rows] => (rows, 1),
+        _ => return Err(ParseError::ShapeError(ShapeError::from_kind(ErrorKind::IncompatibleShape))),
+    };
+    let fits = |dim: Option<usize>, len: usize| dim.map_or(true, |dim| dim == len);
+    if !fits(R::try_to_usize(), rows) || !fits(C::try_to_usize(), cols) {
+        return Err(ParseError::ShapeError(ShapeError::from_kind(ErrorKind::IncompatibleShape)));
+    }
+    let rows = R::from_usize(rows);
+    let cols = C::from_usize(cols);
+    Ok(OMatrix::from_row_iterator_generic(rows, cols, array.iter().copied()))
+}
+
+/// Write a matrix to a file as a SANE-encoded array of shape `[R, C]`
+pub fn write_sane_matrix<F, T, R, C, S>(file: &mut F, matrix: &Matrix<T, R, C, S>) -> Result<(), WriteError>
+where
+    F: Write,
+    T: SaneElement,
+    R: Dim,
+    C: Dim,
+    S: RawStorage<T, R, C>,
+{
+    write_sane(file, &matrix_view(matrix))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use nalgebra::{DMatrix, DVector, Matrix2x3, SMatrix};
+
+    use super::{matrix_view, read_sane_matrix, write_sane_matrix};
+    use crate::read::{read_sane, ParseError};
+    use crate::write::write_sane;
+
+    #[test]
+    fn matrix_layout() {
+        let matrix = Matrix2x3::new(1, 2, 3, 4, 5, 6);
+        let mut file = Cursor::new(Vec::new());
+        write_sane_matrix(&mut file, &matrix).unwrap();
+        file.set_position(0);
+        let array: ndarray::Array2<i32> = read_sane(&mut file).unwrap();
+        assert_eq!(array, ndarray::array![[1, 2, 3], [4, 5, 6]]);
+
+        file.set_position(0);
+        let dynamic: DMatrix<i32> = read_sane_matrix(&mut file).unwrap();
+        assert_eq!(dynamic, matrix);
+        file.set_position(0);
+        let fixed: SMatrix<i32, 2, 3> = read_sane_matrix(&mut file).unwrap();
+        assert_eq!(fixed, matrix);
+        file.set_position(0);
+        let wrong = read_sane_matrix::<_, i32, nalgebra::U3, nalgebra::U2>(&mut file);
+        assert!(matches!(wrong, Err(ParseError::ShapeError(_))));
+    }
+
+    #[test]
+    fn views_and_vectors() {
+        let matrix = DMatrix::from_fn(4, 5, |i, j| (i * 10 + j) as f64);
+        let view = matrix.view((1, 1), (2, 3));
+        assert_eq!(matrix_view(&view), ndarray::array![[11.0, 12.0, 13.0], [21.0, 22.0, 23.0]]);
+        let mut file = Cursor::new(Vec::new());
+        write_sane_matrix(&mut file, &view.transpose()).unwrap();
+        file.set_position(0);
+        let read: DMatrix<f64> = read_sane_matrix(&mut file).unwrap();
+        assert_eq!(read, view.transpose());
+
+        let mut file = Cursor::new(Vec::new());
+        write_sane(&mut file, &ndarray::array![1u8, 2, 3]).unwrap();
+        file.set_position(0);
+        let vector: DVector<u8> = read_sane_matrix(&mut file).unwrap();
+        assert_eq!(vector, DVector::from_vec(vec![1, 2, 3]));
+    }
+}
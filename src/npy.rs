@@ -0,0 +1,415 @@
+//! Conversion between NumPy [`.npy` files](https://numpy.org/doc/stable/reference/generated/numpy.lib.format.html)
+//! and [`Sane`] arrays
+use std::io::{Read, Write};
+
+use crate::data::{DataType, Sane, SaneView};
+use crate::element::swap_bytes;
+use crate::raw::swap_unit;
+use crate::read::{decode_sane, ParseError};
+use crate::write::data_bytes;
+
+const MAGIC: &[u8] = b"\x93NUMPY";
+
+/// The header of each `.npy` file is padded to a multiple of this many bytes
+const HEADER_ALIGNMENT: usize = 64;
+
+#[derive(Debug)]
+pub enum NpyError {
+    Io(std::io::Error),
+    NotNpy,
+    UnsupportedVersion(u8, u8),
+    InvalidHeader(String),
+    UnsupportedDtype(String),
+    UnsupportedDataType(DataType),
+    Parse(ParseError),
+}
+
+impl std::fmt::Display for NpyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        use NpyError::*;
+        match self {
+            Io(err) => write!(f, "I/O error: {}", err),
+            NotNpy => write!(f, "Not a NumPy .npy file"),
+            UnsupportedVersion(major, minor) => write!(f, "Unsupported .npy format version {}.{}", major, minor),
+            InvalidHeader(reason) => write!(f, "Invalid .npy header: {}", reason),
+            UnsupportedDtype(descr) => write!(f, "Unsupported NumPy dtype {}", descr),
+            UnsupportedDataType(data_type) => write!(f, "Data type {} has no NumPy equivalent", data_type),
+            Parse(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for NpyError {}
+
+/// The subset of Python literals that occurs in `.npy` headers
+#[derive(Debug, Clone, PartialEq)]
+enum PyValue {
+    Str(String),
+    Int(usize),
+    Bool(bool),
+    None,
+    Tuple(Vec<PyValue>),
+    List(Vec<PyValue>),
+    Dict(Vec<(PyValue, PyValue)>),
+}
+
+struct PyParser<'a> {
+    input: &'a str,
+    pos: usize,
+}
+
+impl<'a> PyParser<'a> {
+    fn error(&self, reason: &str) -> NpyError {
+        NpyError::InvalidHeader(format!("{} at offset {}", reason, self.pos))
+    }
+
+    /// Skip whitespace and return the next character without consuming it
+    fn peek(&mut self) -> Option<char> {
+        let rest = &self.input[self.pos..];
+        self.pos += rest.len() - rest.trim_start().len();
+        self.input[self.pos..].chars().next()
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), NpyError> {
+        if self.peek() == Some(expected) {
+            self.pos += expected.len_utf8();
+            Ok(())
+        } else {
+            Err(self.error(&format!("expected '{}'", expected)))
+        }
+    }
+
+    /// Parse comma-separated values up to `close`, allowing a trailing comma
+    fn parse_sequence(&mut self, close: char) -> Result<Vec<PyValue>, NpyError> {
+        let mut values = vec![];
+        loop {
+            if self.peek() == Some(close) {
+                self.pos += 1;
+                return Ok(values);
+            }
+            values.push(self.parse_value()?);
+            if self.peek() == Some(',') {
+                self.pos += 1;
+            } else {
+                self.expect(close)?;
+                return Ok(values);
+            }
+        }
+    }
+
+    fn parse_value(&mut self) -> Result<PyValue, NpyError> {
+        match self.peek() {
+            Some('{') => {
+                self.pos += 1;
+                let mut entries = vec![];
+                loop {
+                    if self.peek() == Some('}') {
+                        self.pos += 1;
+                        return Ok(PyValue::Dict(entries));
+                    }
+                    let key = self.parse_value()?;
+                    self.expect(':')?;
+                    let value = self.parse_value()?;
+                    entries.push((key, value));
+                    if self.peek() == Some(',') {
+                        self.pos += 1;
+                    } else {
+                        self.expect('}')?;
+                        return Ok(PyValue::Dict(entries));
+                    }
+                }
+            }
+            Some('(') => {
+                self.pos += 1;
+                self.parse_sequence(')').map(PyValue::Tuple)
+            }
+            Some('[') => {
+                self.pos += 1;
+                self.parse_sequence(']').map(PyValue::List)
+            }
+            Some(quote @ ('\'' | '"')) => {
+                let start = self.pos + 1;
+                let len = self.input[start..].find(quote).ok_or_else(|| self.error("unterminated string"))?;
+                self.pos = start + len + 1;
+                Ok(PyValue::Str(self.input[start..start + len].to_string()))
+            }
+            Some(c) if c.is_ascii_digit() => {
+                let rest = &self.input[self.pos..];
+                let len = rest.find(|c: char| !c.is_ascii_digit()).unwrap_or(rest.len());
+                let value = rest[..len].parse().map_err(|_| self.error("integer too large"))?;
+                self.pos += len;
+                // Python 2 long integers
+                if self.input[self.pos..].starts_with('L') {
+                    self.pos += 1;
+                }
+                Ok(PyValue::Int(value))
+            }
+            Some(_) => {
+                let rest = &self.input[self.pos..];
+                let len = rest.find(|c: char| !c.is_alphanumeric()).unwrap_or(rest.len());
+                let value = match &rest[..len] {
+                    "True" => PyValue::Bool(true),
+                    "False" => PyValue::Bool(false),
+                    "None" => PyValue::None,
+                    _ => return Err(self.error("unexpected token")),
+                };
+                self.pos += len;
+                Ok(value)
+            }
+            None => Err(self.error("unexpected end of header")),
+        }
+    }
+}
+
+/// The contents of an `.npy` header
+struct NpyHeader {
+    data_type: DataType,
+    big_endian: bool,
+    fortran_order: bool,
+    shape: Vec<usize>,
+}
+
+/// Map a NumPy type string such as `"<f4"` onto a [`DataType`] and whether it is big-endian
+fn parse_descr(descr: &str) -> Result<(DataType, bool), NpyError> {
+    let unsupported = || NpyError::UnsupportedDtype(descr.to_string());
+    let (prefix, rest) = if descr.is_char_boundary(1) { descr.split_at(1) } else { ("", descr) };
+    let (big_endian, type_string) = match prefix {
+        "<" | "|" => (false, rest),
+        ">" => (true, rest),
+        "=" => (cfg!(target_endian = "big"), rest),
+        _ => (cfg!(target_endian = "big"), descr),
+    };
+    let data_type = format!("<{}", type_string).parse().map_err(|_| unsupported())?;
+    Ok((data_type, big_endian))
+}
+
+fn parse_header(header: &str) -> Result<NpyHeader, NpyError> {
+    let mut parser = PyParser { input: header, pos: 0 };
+    let entries = match parser.parse_value()? {
+        PyValue::Dict(entries) => entries,
+        _ => return Err(NpyError::InvalidHeader("header is not a dictionary".to_string())),
+    };
+    let get = |key: &str| {
+        entries.iter()
+            .find(|(k, _)| *k == PyValue::Str(key.to_string()))
+            .map(|(_, value)| value)
+            .ok_or_else(|| NpyError::InvalidHeader(format!("missing key '{}'", key)))
+    };
+    let (data_type, big_endian) = match get("descr")? {
+        PyValue::Str(descr) => parse_descr(descr)?,
+        other => return Err(NpyError::UnsupportedDtype(format!("{:?}", other))),
+    };
+    let fortran_order = match get("fortran_order")? {
+        PyValue::Bool(fortran_order) => *fortran_order,
+        _ => return Err(NpyError::InvalidHeader("'fortran_order' is not a boolean".to_string())),
+    };
+    let shape = match get("shape")? {
+        PyValue::Tuple(dims) => dims.iter().map(|dim| match dim {
+            PyValue::Int(dim) => Ok(*dim),
+            _ => Err(NpyError::InvalidHeader("'shape' contains a non-integer".to_string())),
+        }).collect::<Result<_, _>>()?,
+        _ => return Err(NpyError::InvalidHeader("'shape' is not a tuple".to_string())),
+    };
+    Ok(NpyHeader { data_type, big_endian, fortran_order, shape })
+}
+
+fn read_header<R: Read>(reader: &mut R) -> Result<NpyHeader, NpyError> {
+    let mut preamble = [0u8; 8];
+    reader.read_exact(&mut preamble).map_err(NpyError::Io)?;
+    if &preamble[..6] != MAGIC {
+        return Err(NpyError::NotNpy);
+    }
+    let header_len = match (preamble[6], preamble[7]) {
+        (1, 0) => {
+            let mut len = [0u8; 2];
+            reader.read_exact(&mut len).map_err(NpyError::Io)?;
+            usize::from(u16::from_le_bytes(len))
+        }
+        (2 | 3, 0) => {
+            let mut len = [0u8; 4];
+            reader.read_exact(&mut len).map_err(NpyError::Io)?;
+            usize::try_from(u32::from_le_bytes(len))
+                .map_err(|_| NpyError::InvalidHeader("header too long".to_string()))?
+        }
+        (major, minor) => return Err(NpyError::UnsupportedVersion(major, minor)),
+    };
+    let mut header = vec![0u8; header_len];
+    reader.read_exact(&mut header).map_err(NpyError::Io)?;
+    // Version 1 and 2 headers are latin-1 but only contain ASCII in practice, version 3 is UTF-8
+    let header = String::from_utf8(header)
+        .map_err(|_| NpyError::InvalidHeader("header is not valid text".to_string()))?;
+    parse_header(&header)
+}
+
+/// Read a `.npy` file into a [`Sane`] array
+///
+/// Big-endian data is converted to native byte order and Fortran-ordered data to row-major
+/// order.
+pub fn read_npy<R: Read>(reader: &mut R) -> Result<Sane, NpyError> {
+    let header = read_header(reader)?;
+    let element_size = header.data_type.size_in_bytes();
+    let byte_length = header.shape.iter()
+        .try_fold(element_size, |len, &dim| len.checked_mul(dim))
+        .ok_or_else(|| NpyError::InvalidHeader("array too large".to_string()))?;
+    // Read the bytes before allocating for them, in case the shape is bogus
+    let mut data = vec![];
+    reader.take(byte_length as u64).read_to_end(&mut data).map_err(NpyError::Io)?;
+    if data.len() != byte_length {
+        return Err(NpyError::Io(std::io::ErrorKind::UnexpectedEof.into()));
+    }
+    if header.big_endian {
+        swap_bytes(&mut data, swap_unit(header.data_type));
+    }
+    if header.fortran_order {
+        let reversed: Vec<usize> = header.shape.iter().rev().copied().collect();
+        let sane = decode_sane(&reversed, header.data_type, &data).map_err(NpyError::Parse)?;
+        Ok(crate::sane_dispatch!(Sane: sane, array => {
+            Sane::from(array.reversed_axes().as_standard_layout().into_owned())
+        }))
+    } else {
+        decode_sane(&header.shape, header.data_type, &data).map_err(NpyError::Parse)
+    }
+}
+
+/// The NumPy type string of a [`DataType`], if NumPy has an equivalent type
+fn npy_descr(data_type: DataType) -> Option<&'static str> {
+    use DataType::*;
+    match data_type {
+        F32 => Some("<f4"),
+        I32 => Some("<i4"),
+        U32 => Some("<u4"),
+        F64 => Some("<f8"),
+        I64 => Some("<i8"),
+        U64 => Some("<u8"),
+        I8 => Some("|i1"),
+        U8 => Some("|u1"),
+        I16 => Some("<i2"),
+        U16 => Some("<u2"),
+        F16 => Some("<f2"),
+        BF16 => None,
+        C64 => Some("<c8"),
+        C128 => Some("<c16"),
+        Bool => Some("|b1"),
+    }
+}
+
+/// Write an array as a `.npy` file in little-endian C order
+pub fn write_npy<'a, W: Write, S: Into<SaneView<'a>>>(writer: &mut W, sane: S) -> Result<(), NpyError> {
+    let sane = sane.into();
+    let data_type = sane.data_type();
+    let descr = npy_descr(data_type).ok_or(NpyError::UnsupportedDataType(data_type))?;
+    let shape = match sane.shape() {
+        [dim] => format!("({},)", dim),
+        dims => format!("({})", dims.iter().map(usize::to_string).collect::<Vec<_>>().join(", ")),
+    };
+    let mut header = format!("{{'descr': '{}', 'fortran_order': False, 'shape': {}, }}", descr, shape);
+    // Version 1 stores the header length in 16 bits, which is enough for all but very high ranks
+    let (version, len_bytes) = if header.len() + HEADER_ALIGNMENT <= usize::from(u16::MAX) {
+        (1, 2)
+    } else {
+        (2, 4)
+    };
+    // Pad with spaces and a final newline so that the data starts at an aligned offset
+    let unpadded = MAGIC.len() + 2 + len_bytes + header.len() + 1;
+    let padding = (HEADER_ALIGNMENT - unpadded % HEADER_ALIGNMENT) % HEADER_ALIGNMENT;
+    header.extend(std::iter::repeat(' ').take(padding));
+    header.push('\n');
+    let mut preamble = MAGIC.to_vec();
+    preamble.extend_from_slice(&[version, 0]);
+    if version == 1 {
+        preamble.extend_from_slice(&(header.len() as u16).to_le_bytes());
+    } else {
+        let header_len = u32::try_from(header.len())
+            .map_err(|_| NpyError::InvalidHeader("header too long".to_string()))?;
+        preamble.extend_from_slice(&header_len.to_le_bytes());
+    }
+    preamble.extend_from_slice(header.as_bytes());
+    writer.write_all(&preamble).map_err(NpyError::Io)?;
+    let data = crate::sane_dispatch!(SaneView: &sane, array => data_bytes(array));
+    writer.write_all(&data).map_err(NpyError::Io)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::{read_npy, write_npy, NpyError};
+    use crate::Sane;
+
+    #[test]
+    fn roundtrip_npy() {
+        let arrs = vec![
+            Sane::from(ndarray::array![[1i32, 2, 3], [4, 5, 6]]),
+            Sane::from(ndarray::array![0.5f64]),
+            Sane::from(ndarray::arr0(true)),
+        ];
+        for sane in arrs {
+            let mut file = Vec::new();
+            write_npy(&mut file, &sane).unwrap();
+            assert_eq!(file.len() % 64, sane.byte_len() % 64);
+            assert_eq!(read_npy(&mut Cursor::new(file)).unwrap(), sane);
+        }
+    }
+
+    #[test]
+    fn big_endian_fortran_order() {
+        let header = "{'descr': '>u2', 'fortran_order': True, 'shape': (2, 3), }";
+        let mut file = b"\x93NUMPY\x01\x00".to_vec();
+        file.extend_from_slice(&(header.len() as u16).to_le_bytes());
+        file.extend_from_slice(header.as_bytes());
+        // Column-major: [[1, 2, 3], [4, 5, 6]] is stored as 1, 4, 2, 5, 3, 6
+        for value in [1u16, 4, 2, 5, 3, 6] {
+            file.extend_from_slice(&value.to_be_bytes());
+        }
+        let sane = read_npy(&mut Cursor::new(file)).unwrap();
+        assert_eq!(sane, Sane::from(ndarray::array![[1u16, 2, 3], [4, 5, 6]]));
+    }
+
+    #[test]
+    fn truncated_data() {
+        let header = "{'descr': '<f8', 'fortran_order': False, 'shape': (1000000, 1000000), }";
+        let mut file = b"\x93NUMPY\x01\x00".to_vec();
+        file.extend_from_slice(&(header.len() as u16).to_le_bytes());
+        file.extend_from_slice(header.as_bytes());
+        file.extend_from_slice(&[0; 16]);
+        let result = read_npy(&mut Cursor::new(file));
+        assert!(matches!(result, Err(NpyError::Io(err)) if err.kind() == std::io::ErrorKind::UnexpectedEof));
+    }
+
+    #[test]
+    fn unsupported_dtype() {
+        let header = "{'descr': '<U10', 'fortran_order': False, 'shape': (1,), }";
+        let mut file = b"\x93NUMPY\x01\x00".to_vec();
+        file.extend_from_slice(&(header.len() as u16).to_le_bytes());
+        file.extend_from_slice(header.as_bytes());
+        let result = read_npy(&mut Cursor::new(file));
+        assert!(matches!(result, Err(NpyError::UnsupportedDtype(descr)) if descr == "<U10"));
+    }
+
+    #[test]
+    fn long_header_uses_version_2() {
+        // Every axis adds at least three characters to the shape tuple
+        let shape = vec![1; 22_000];
+        let sane = Sane::from(ndarray::ArrayD::from_elem(ndarray::IxDyn(&shape), 7u8));
+        let mut file = Vec::new();
+        write_npy(&mut file, &sane).unwrap();
+        assert_eq!(&file[6..8], &[2, 0]);
+        let header_len = u32::from_le_bytes(file[8..12].try_into().unwrap()) as usize;
+        assert!(header_len > usize::from(u16::MAX));
+        assert_eq!((12 + header_len) % 64, 0);
+        assert_eq!(read_npy(&mut Cursor::new(file)).unwrap(), sane);
+    }
+
+    #[test]
+    fn version_3_utf8_header() {
+        let header = "{'descr': '<i2', 'fortran_order': False, 'shape': (2,), 'n\u{f6}te': 'gr\u{f6}\u{df}e \u{1f4d0}', }\n";
+        let mut file = b"\x93NUMPY\x03\x00".to_vec();
+        file.extend_from_slice(&(header.len() as u32).to_le_bytes());
+        file.extend_from_slice(header.as_bytes());
+        for value in [-3i16, 300] {
+            file.extend_from_slice(&value.to_le_bytes());
+        }
+        let sane = read_npy(&mut Cursor::new(file)).unwrap();
+        assert_eq!(sane, Sane::from(ndarray::array![-3i16, 300]));
+    }
+}
@@ -0,0 +1,103 @@
+//! Reading and writing NumPy `.npz` archives of named arrays
+use std::collections::BTreeMap;
+use std::io::{Read, Seek, Write};
+
+use zip::result::ZipError;
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+use crate::data::{Sane, SaneView};
+use crate::npy::{read_npy, write_npy, NpyError};
+
+/// How the arrays in an `.npz` archive are compressed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum NpzCompression {
+    /// Store the arrays uncompressed, like `numpy.savez`
+    #[default]
+    Stored,
+    /// Compress the arrays with deflate, like `numpy.savez_compressed`
+    Deflated,
+}
+
+#[derive(Debug)]
+pub enum NpzError {
+    Zip(ZipError),
+    Npy(String, NpyError),
+}
+
+impl std::fmt::Display for NpzError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        use NpzError::*;
+        match self {
+            Zip(err) => write!(f, "Invalid .npz archive: {}", err),
+            Npy(name, err) => write!(f, "Array {}: {}", name, err),
+        }
+    }
+}
+
+impl std::error::Error for NpzError {}
+
+/// Read all arrays of an `.npz` archive, keyed by their names without the `.npy` extension
+pub fn read_npz<R: Read + Seek>(reader: R) -> Result<BTreeMap<String, Sane>, NpzError> {
+    let mut archive = ZipArchive::new(reader).map_err(NpzError::Zip)?;
+    let mut arrays = BTreeMap::new();
+    for index in 0..archive.len() {
+        let mut file = archive.by_index(index).map_err(NpzError::Zip)?;
+        if file.is_dir() {
+            continue;
+        }
+        let name = file.name();
+        let name = name.strip_suffix(".npy").unwrap_or(name).to_string();
+        let sane = read_npy(&mut file).map_err(|err| NpzError::Npy(name.clone(), err))?;
+        arrays.insert(name, sane);
+    }
+    Ok(arrays)
+}
+
+/// Write named arrays to an `.npz` archive, each stored as `<name>.npy`
+pub fn write_npz<'a, W, Arrays, K, S>(writer: W, arrays: Arrays, compression: NpzCompression) -> Result<(), NpzError>
+where
+    W: Write + Seek,
+    Arrays: IntoIterator<Item = (K, S)>,
+    K: AsRef<str>,
+    S: Into<SaneView<'a>>
+{
+    let method = match compression {
+        NpzCompression::Stored => CompressionMethod::Stored,
+        NpzCompression::Deflated => CompressionMethod::Deflated,
+    };
+    let mut zip = ZipWriter::new(writer);
+    for (name, sane) in arrays {
+        let name = name.as_ref();
+        let sane = sane.into();
+        // Leave room for the .npy header when deciding whether ZIP64 is needed
+        let large_file = sane.encoded_len() as u64 + 1024 > u64::from(u32::MAX);
+        let options = SimpleFileOptions::default().compression_method(method).large_file(large_file);
+        zip.start_file(format!("{}.npy", name), options).map_err(NpzError::Zip)?;
+        write_npy(&mut zip, sane).map_err(|err| NpzError::Npy(name.to_string(), err))?;
+    }
+    zip.finish().map_err(NpzError::Zip)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+    use std::io::Cursor;
+
+    use super::{read_npz, write_npz, NpzCompression};
+    use crate::Sane;
+
+    #[test]
+    fn roundtrip_npz() {
+        let mut arrays = BTreeMap::new();
+        arrays.insert("weights".to_string(), Sane::from(ndarray::Array::linspace(0.0f32, 1.0, 100)));
+        arrays.insert("mask".to_string(), Sane::from(ndarray::array![[true, false], [false, true]]));
+        for compression in [NpzCompression::Stored, NpzCompression::Deflated] {
+            let mut file = Cursor::new(Vec::new());
+            write_npz(&mut file, &arrays, compression).unwrap();
+            file.set_position(0);
+            assert_eq!(read_npz(file).unwrap(), arrays);
+        }
+    }
+}
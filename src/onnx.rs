@@ -0,0 +1,251 @@
+//! Conversion between [`Sane`] arrays and [ONNX](https://onnx.ai) `TensorProto` messages
+//!
+//! The message structs are generated with prost-build from `proto/onnx.proto`, which is vendored
+//! from [upstream](https://github.com/onnx/onnx/blob/main/onnx/onnx.proto) and covers
+//! `TensorProto` and the messages it uses. A test checks that the generated code is up to date
+//! with the vendored definitions. Arrays are written with their elements as little-endian
+//! `raw_data`. When reading, the typed data fields such as `float_data` and `int32_data` are
+//! supported as well, but tensors stored in external files are not.
+use std::io::{Read, Write};
+
+use prost::Message;
+
+use crate::data::{DataType, Sane, SaneView};
+use crate::raw::swap_unit;
+use crate::read::{decode_sane, ParseError};
+use crate::write::data_bytes;
+
+mod proto;
+
+pub use proto::{tensor_proto, StringStringEntryProto, TensorProto};
+
+#[derive(Debug)]
+pub enum OnnxError {
+    Io(std::io::Error),
+    Decode(prost::DecodeError),
+    /// A tensor with an ONNX data type that SANE does not support, such as strings
+    UnsupportedDataType(i32),
+    /// A tensor whose data is stored in an external file
+    ExternalData,
+    InvalidTensor(String),
+    Parse(ParseError),
+}
+
+impl std::fmt::Display for OnnxError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        use OnnxError::*;
+        match self {
+            Io(err) => write!(f, "I/O error: {}", err),
+            Decode(err) => write!(f, "Invalid TensorProto: {}", err),
+            UnsupportedDataType(data_type) => write!(f, "Unsupported ONNX data type {}", data_type),
+            ExternalData => write!(f, "Tensors with external data are not supported"),
+            InvalidTensor(reason) => write!(f, "Invalid TensorProto: {}", reason),
+            Parse(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for OnnxError {}
+
+/// The ONNX data type of a data type
+pub fn onnx_data_type(data_type: DataType) -> tensor_proto::DataType {
+    use tensor_proto::DataType as Onnx;
+    match data_type {
+        DataType::F32 => Onnx::Float,
+        DataType::I32 => Onnx::Int32,
+        DataType::U32 => Onnx::Uint32,
+        DataType::F64 => Onnx::Double,
+        DataType::I64 => Onnx::Int64,
+        DataType::U64 => Onnx::Uint64,
+        DataType::I8 => Onnx::Int8,
+        DataType::U8 => Onnx::Uint8,
+        DataType::I16 => Onnx::Int16,
+        DataType::U16 => Onnx::Uint16,
+        DataType::F16 => Onnx::Float16,
+        DataType::BF16 => Onnx::Bfloat16,
+        DataType::C64 => Onnx::Complex64,
+        DataType::C128 => Onnx::Complex128,
+        DataType::Bool => Onnx::Bool,
+    }
+}
+
+/// The data type of an ONNX data type, if there is one
+pub fn from_onnx_data_type(data_type: tensor_proto::DataType) -> Option<DataType> {
+    use tensor_proto::DataType as Onnx;
+    Some(match data_type {
+        Onnx::Float => DataType::F32,
+        Onnx::Int32 => DataType::I32,
+        Onnx::Uint32 => DataType::U32,
+        Onnx::Double => DataType::F64,
+        Onnx::Int64 => DataType::I64,
+        Onnx::Uint64 => DataType::U64,
+        Onnx::Int8 => DataType::I8,
+        Onnx::Uint8 => DataType::U8,
+        Onnx::Int16 => DataType::I16,
+        Onnx::Uint16 => DataType::U16,
+        Onnx::Float16 => DataType::F16,
+        Onnx::Bfloat16 => DataType::BF16,
+        Onnx::Complex64 => DataType::C64,
+        Onnx::Complex128 => DataType::C128,
+        Onnx::Bool => DataType::Bool,
+        _ => return None,
+    })
+}
+
+/// Convert an array to a tensor with the given name and its elements as `raw_data`
+pub fn to_tensor_proto<'a, S: Into<SaneView<'a>>>(name: &str, sane: S) -> Result<TensorProto, OnnxError> {
+    let sane = sane.into();
+    let dims = sane.shape().iter()
+        .map(|&dim| i64::try_from(dim).map_err(|_| OnnxError::InvalidTensor(format!("dimension {} too large", dim))))
+        .collect::<Result<_, _>>()?;
+    let raw_data = crate::sane_dispatch!(SaneView: &sane, array => data_bytes(array)).into_owned();
+    Ok(TensorProto {
+        dims,
+        data_type: Some(onnx_data_type(sane.data_type()) as i32),
+        name: Some(name.to_string()),
+        raw_data: Some(raw_data),
+        ..TensorProto::default()
+    })
+}
+
+/// The little-endian bytes of the typed data field that holds elements of the given data type
+///
+/// Elements narrower than the values of their field, such as `int8` elements in `int32_data`, are
+/// stored in the low bits of each value.
+fn typed_data_bytes(tensor: &TensorProto, data_type: DataType) -> Vec<u8> {
+    let unit = swap_unit(data_type);
+    match data_type {
+        DataType::F32 | DataType::C64 => tensor.float_data.iter().flat_map(|value| value.to_le_bytes()).collect(),
+        DataType::F64 | DataType::C128 => tensor.double_data.iter().flat_map(|value| value.to_le_bytes()).collect(),
+        DataType::I64 => tensor.int64_data.iter().flat_map(|value| value.to_le_bytes()).collect(),
+        DataType::U32 | DataType::U64 => tensor.uint64_data.iter()
+            .flat_map(|value| value.to_le_bytes().into_iter().take(unit))
+            .collect(),
+        _ => tensor.int32_data.iter()
+            .flat_map(|value| value.to_le_bytes().into_iter().take(unit))
+            .collect(),
+    }
+}
+
+/// Convert a tensor to an array, from its `raw_data` if present and its typed data field
+/// otherwise
+pub fn from_tensor_proto(tensor: &TensorProto) -> Result<Sane, OnnxError> {
+    if tensor.data_location == Some(tensor_proto::DataLocation::External as i32) {
+        return Err(OnnxError::ExternalData);
+    }
+    let code = tensor.data_type.unwrap_or_default();
+    let data_type = tensor_proto::DataType::try_from(code).ok()
+        .and_then(from_onnx_data_type)
+        .ok_or(OnnxError::UnsupportedDataType(code))?;
+    let shape: Vec<usize> = tensor.dims.iter()
+        .map(|&dim| usize::try_from(dim).map_err(|_| OnnxError::InvalidTensor(format!("invalid dimension {}", dim))))
+        .collect::<Result<_, _>>()?;
+    match &tensor.raw_data {
+        Some(raw_data) => decode_sane(&shape, data_type, raw_data),
+        None => decode_sane(&shape, data_type, &typed_data_bytes(tensor, data_type)),
+    }.map_err(OnnxError::Parse)
+}
+
+/// Read a serialized `TensorProto`, such as a `.pb` test fixture, into an array
+pub fn read_tensor_proto<R: Read>(reader: &mut R) -> Result<Sane, OnnxError> {
+    let mut buffer = vec![];
+    reader.read_to_end(&mut buffer).map_err(OnnxError::Io)?;
+    let tensor = TensorProto::decode(buffer.as_slice()).map_err(OnnxError::Decode)?;
+    from_tensor_proto(&tensor)
+}
+
+/// Write an array as a serialized `TensorProto` with the given name
+pub fn write_tensor_proto<'a, W: Write, S: Into<SaneView<'a>>>(writer: &mut W, name: &str, sane: S) -> Result<(), OnnxError> {
+    let tensor = to_tensor_proto(name, sane)?;
+    writer.write_all(&tensor.encode_to_vec()).map_err(OnnxError::Io)
+}
+
+#[cfg(test)]
+mod tests {
+    use ndarray::array;
+
+    use super::{from_tensor_proto, read_tensor_proto, tensor_proto, to_tensor_proto, write_tensor_proto, OnnxError, TensorProto};
+    use crate::Sane;
+
+    #[test]
+    fn roundtrip_tensor_proto() {
+        let arrays = [
+            Sane::from(array![[1.5f32, -2.0], [0.0, 3.25]]),
+            Sane::from(array![[[true, false]]]),
+            Sane::from(ndarray::arr0(-5i64).into_dyn()),
+            Sane::from(array![1u16, 65535]),
+        ];
+        for sane in arrays {
+            let mut buffer = vec![];
+            write_tensor_proto(&mut buffer, "fixture", &sane).unwrap();
+            assert_eq!(read_tensor_proto(&mut buffer.as_slice()).unwrap(), sane);
+        }
+        let tensor = to_tensor_proto("weights", &Sane::from(array![[1u8, 2, 3]])).unwrap();
+        assert_eq!(tensor.dims, [1, 3]);
+        assert_eq!(tensor.data_type, Some(tensor_proto::DataType::Uint8 as i32));
+        assert_eq!(tensor.raw_data.as_deref(), Some(&[1, 2, 3][..]));
+    }
+
+    #[test]
+    fn typed_data_fields() {
+        // Unpacked dims 2 and 3, data type INT8 and packed int32_data
+        let bytes = [0x08, 0x02, 0x08, 0x03, 0x10, 0x03, 0x2a, 0x0f, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x01, 0, 1, 2, 3, 4];
+        let sane = read_tensor_proto(&mut bytes.as_slice()).unwrap();
+        assert_eq!(sane, Sane::from(array![[-1i8, 0, 1], [2, 3, 4]]));
+
+        let tensor = TensorProto {
+            dims: vec![2],
+            data_type: Some(tensor_proto::DataType::Uint32 as i32),
+            uint64_data: vec![7, 4_000_000_000],
+            ..TensorProto::default()
+        };
+        assert_eq!(from_tensor_proto(&tensor).unwrap(), Sane::from(array![7u32, 4_000_000_000]));
+    }
+
+    #[test]
+    fn unsupported_tensors() {
+        let strings = TensorProto {
+            dims: vec![1],
+            data_type: Some(tensor_proto::DataType::String as i32),
+            string_data: vec![b"text".to_vec()],
+            ..TensorProto::default()
+        };
+        assert!(matches!(from_tensor_proto(&strings), Err(OnnxError::UnsupportedDataType(8))));
+        let external = TensorProto {
+            data_type: Some(tensor_proto::DataType::Float as i32),
+            data_location: Some(tensor_proto::DataLocation::External as i32),
+            ..TensorProto::default()
+        };
+        assert!(matches!(from_tensor_proto(&external), Err(OnnxError::ExternalData)));
+    }
+
+    /// Regenerate the message structs from the vendored `onnx.proto` and compare them with the
+    /// checked-in code, overwriting it if `SANE_UPDATE_PROTO` is set
+    #[test]
+    fn generated_code_is_up_to_date() {
+        use prost::Message;
+        use protobuf::Message as _;
+
+        let root = std::path::Path::new(env!("CARGO_MANIFEST_DIR"));
+        let parsed = protobuf_parse::Parser::new()
+            .pure()
+            .include(root.join("proto"))
+            .input(root.join("proto/onnx.proto"))
+            .parse_and_typecheck()
+            .unwrap();
+        let mut descriptors = protobuf::descriptor::FileDescriptorSet::new();
+        descriptors.file = parsed.file_descriptors;
+        let descriptors = prost_types::FileDescriptorSet::decode(descriptors.write_to_bytes().unwrap().as_slice()).unwrap();
+        let out_dir = std::env::temp_dir().join(format!("sane-onnx-proto-{}", std::process::id()));
+        std::fs::create_dir_all(&out_dir).unwrap();
+        prost_build::Config::new().out_dir(&out_dir).compile_fds(descriptors).unwrap();
+        let generated = std::fs::read_to_string(out_dir.join("onnx.rs")).unwrap();
+        std::fs::remove_dir_all(&out_dir).unwrap();
+
+        let checked_in = root.join("src/onnx/proto.rs");
+        if std::env::var_os("SANE_UPDATE_PROTO").is_some() {
+            std::fs::write(&checked_in, &generated).unwrap();
+        }
+        assert!(generated == std::fs::read_to_string(&checked_in).unwrap(), "src/onnx/proto.rs is out of date, run the tests with SANE_UPDATE_PROTO=1");
+    }
+}
@@ -0,0 +1,189 @@
+// This file is @generated by prost-build.
+#[derive(Clone, PartialEq, Eq, Hash, ::prost::Message)]
+pub struct StringStringEntryProto {
+    #[prost(string, optional, tag = "1")]
+    pub key: ::core::option::Option<::prost::alloc::string::String>,
+    #[prost(string, optional, tag = "2")]
+    pub value: ::core::option::Option<::prost::alloc::string::String>,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct TensorProto {
+    #[prost(int64, repeated, packed = "false", tag = "1")]
+    pub dims: ::prost::alloc::vec::Vec<i64>,
+    #[prost(int32, optional, tag = "2")]
+    pub data_type: ::core::option::Option<i32>,
+    #[prost(message, optional, tag = "3")]
+    pub segment: ::core::option::Option<tensor_proto::Segment>,
+    #[prost(float, repeated, tag = "4")]
+    pub float_data: ::prost::alloc::vec::Vec<f32>,
+    #[prost(int32, repeated, tag = "5")]
+    pub int32_data: ::prost::alloc::vec::Vec<i32>,
+    #[prost(bytes = "vec", repeated, tag = "6")]
+    pub string_data: ::prost::alloc::vec::Vec<::prost::alloc::vec::Vec<u8>>,
+    #[prost(int64, repeated, tag = "7")]
+    pub int64_data: ::prost::alloc::vec::Vec<i64>,
+    #[prost(string, optional, tag = "8")]
+    pub name: ::core::option::Option<::prost::alloc::string::String>,
+    #[prost(string, optional, tag = "12")]
+    pub doc_string: ::core::option::Option<::prost::alloc::string::String>,
+    #[prost(bytes = "vec", optional, tag = "9")]
+    pub raw_data: ::core::option::Option<::prost::alloc::vec::Vec<u8>>,
+    #[prost(message, repeated, tag = "13")]
+    pub external_data: ::prost::alloc::vec::Vec<StringStringEntryProto>,
+    #[prost(enumeration = "tensor_proto::DataLocation", optional, tag = "14")]
+    pub data_location: ::core::option::Option<i32>,
+    #[prost(double, repeated, tag = "10")]
+    pub double_data: ::prost::alloc::vec::Vec<f64>,
+    #[prost(uint64, repeated, tag = "11")]
+    pub uint64_data: ::prost::alloc::vec::Vec<u64>,
+    #[prost(message, repeated, tag = "16")]
+    pub metadata_props: ::prost::alloc::vec::Vec<StringStringEntryProto>,
+}
+/// Nested message and enum types in `TensorProto`.
+pub mod tensor_proto {
+    #[derive(Clone, Copy, PartialEq, Eq, Hash, ::prost::Message)]
+    pub struct Segment {
+        #[prost(int64, optional, tag = "1")]
+        pub begin: ::core::option::Option<i64>,
+        #[prost(int64, optional, tag = "2")]
+        pub end: ::core::option::Option<i64>,
+    }
+    #[derive(
+        Clone,
+        Copy,
+        Debug,
+        PartialEq,
+        Eq,
+        Hash,
+        PartialOrd,
+        Ord,
+        ::prost::Enumeration
+    )]
+    #[repr(i32)]
+    pub enum DataType {
+        Undefined = 0,
+        Float = 1,
+        Uint8 = 2,
+        Int8 = 3,
+        Uint16 = 4,
+        Int16 = 5,
+        Int32 = 6,
+        Int64 = 7,
+        String = 8,
+        Bool = 9,
+        Float16 = 10,
+        Double = 11,
+        Uint32 = 12,
+        Uint64 = 13,
+        Complex64 = 14,
+        Complex128 = 15,
+        Bfloat16 = 16,
+        Float8e4m3fn = 17,
+        Float8e4m3fnuz = 18,
+        Float8e5m2 = 19,
+        Float8e5m2fnuz = 20,
+        Uint4 = 21,
+        Int4 = 22,
+        Float4e2m1 = 23,
+    }
+    impl DataType {
+        /// String value of the enum field names used in the ProtoBuf definition.
+        ///
+        /// The values are not transformed in any way and thus are considered stable
+        /// (if the ProtoBuf definition does not change) and safe for programmatic use.
+        pub fn as_str_name(&self) -> &'static str {
+            match self {
+                Self::Undefined => "UNDEFINED",
+                Self::Float => "FLOAT",
+                Self::Uint8 => "UINT8",
+                Self::Int8 => "INT8",
+                Self::Uint16 => "UINT16",
+                Self::Int16 => "INT16",
+                Self::Int32 => "INT32",
+                Self::Int64 => "INT64",
+                Self::String => "STRING",
+                Self::Bool => "BOOL",
+                Self::Float16 => "FLOAT16",
+                Self::Double => "DOUBLE",
+                Self::Uint32 => "UINT32",
+                Self::Uint64 => "UINT64",
+                Self::Complex64 => "COMPLEX64",
+                Self::Complex128 => "COMPLEX128",
+                Self::Bfloat16 => "BFLOAT16",
+                Self::Float8e4m3fn => "FLOAT8E4M3FN",
+                Self::Float8e4m3fnuz => "FLOAT8E4M3FNUZ",
+                Self::Float8e5m2 => "FLOAT8E5M2",
+                Self::Float8e5m2fnuz => "FLOAT8E5M2FNUZ",
+                Self::Uint4 => "UINT4",
+                Self::Int4 => "INT4",
+                Self::Float4e2m1 => "FLOAT4E2M1",
+            }
+        }
+        /// Creates an enum from field names used in the ProtoBuf definition.
+        pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
+            match value {
+                "UNDEFINED" => Some(Self::Undefined),
+                "FLOAT" => Some(Self::Float),
+                "UINT8" => Some(Self::Uint8),
+                "INT8" => Some(Self::Int8),
+                "UINT16" => Some(Self::Uint16),
+                "INT16" => Some(Self::Int16),
+                "INT32" => Some(Self::Int32),
+                "INT64" => Some(Self::Int64),
+                "STRING" => Some(Self::String),
+                "BOOL" => Some(Self::Bool),
+                "FLOAT16" => Some(Self::Float16),
+                "DOUBLE" => Some(Self::Double),
+                "UINT32" => Some(Self::Uint32),
+                "UINT64" => Some(Self::Uint64),
+                "COMPLEX64" => Some(Self::Complex64),
+                "COMPLEX128" => Some(Self::Complex128),
+                "BFLOAT16" => Some(Self::Bfloat16),
+                "FLOAT8E4M3FN" => Some(Self::Float8e4m3fn),
+                "FLOAT8E4M3FNUZ" => Some(Self::Float8e4m3fnuz),
+                "FLOAT8E5M2" => Some(Self::Float8e5m2),
+                "FLOAT8E5M2FNUZ" => Some(Self::Float8e5m2fnuz),
+                "UINT4" => Some(Self::Uint4),
+                "INT4" => Some(Self::Int4),
+                "FLOAT4E2M1" => Some(Self::Float4e2m1),
+                _ => None,
+            }
+        }
+    }
+    #[derive(
+        Clone,
+        Copy,
+        Debug,
+        PartialEq,
+        Eq,
+        Hash,
+        PartialOrd,
+        Ord,
+        ::prost::Enumeration
+    )]
+    #[repr(i32)]
+    pub enum DataLocation {
+        Default = 0,
+        External = 1,
+    }
+    impl DataLocation {
+        /// String value of the enum field names used in the ProtoBuf definition.
+        ///
+        /// The values are not transformed in any way and thus are considered stable
+        /// (if the ProtoBuf definition does not change) and safe for programmatic use.
+        pub fn as_str_name(&self) -> &'static str {
+            match self {
+                Self::Default => "DEFAULT",
+                Self::External => "EXTERNAL",
+            }
+        }
+        /// Creates an enum from field names used in the ProtoBuf definition.
+        pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
+            match value {
+                "DEFAULT" => Some(Self::Default),
+                "EXTERNAL" => Some(Self::External),
+                _ => None,
+            }
+        }
+    }
+}
@@ -0,0 +1,85 @@
+//! Reading and writing headerless binary data, whose shape and data type are known in advance
+use std::io::{Read, Write};
+
+use ndarray::{ErrorKind, ShapeError};
+
+use crate::data::{DataType, Sane, SaneView};
+use crate::element::swap_bytes;
+use crate::read::{decode_sane, ParseError};
+use crate::write::{data_bytes, WriteError};
+
+/// The byte order of the elements of raw data
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Endianness {
+    Little,
+    Big,
+}
+
+impl Endianness {
+    /// The byte order of the target platform
+    pub const NATIVE: Endianness = if cfg!(target_endian = "big") { Endianness::Big } else { Endianness::Little };
+}
+
+/// The size of the scalars whose bytes are swapped, which is half the element size for complex
+/// numbers
+pub(crate) fn swap_unit(data_type: DataType) -> usize {
+    if data_type.is_complex() {
+        data_type.size_in_bytes() / 2
+    } else {
+        data_type.size_in_bytes()
+    }
+}
+
+/// Read exactly as many elements as the shape holds, in row-major order and the given byte order
+pub fn read_raw<R: Read>(reader: &mut R, shape: &[usize], data_type: DataType, endianness: Endianness) -> Result<Sane, ParseError> {
+    let byte_length = shape.iter()
+        .try_fold(data_type.size_in_bytes(), |len, &dim| len.checked_mul(dim))
+        .ok_or_else(|| ParseError::ShapeError(ShapeError::from_kind(ErrorKind::Overflow)))?;
+    let mut data = vec![0u8; byte_length];
+    reader.read_exact(&mut data).map_err(ParseError::NotEnoughBytes)?;
+    if endianness == Endianness::Big {
+        swap_bytes(&mut data, swap_unit(data_type));
+    }
+    decode_sane(shape, data_type, &data)
+}
+
+/// Write the elements of an array in row-major order and the given byte order, without its shape
+/// or data type
+pub fn write_raw<'a, W: Write, S: Into<SaneView<'a>>>(writer: &mut W, sane: S, endianness: Endianness) -> Result<(), WriteError> {
+    let sane = sane.into();
+    let mut data = crate::sane_dispatch!(SaneView: &sane, array => data_bytes(array));
+    if endianness == Endianness::Big {
+        swap_bytes(data.to_mut(), swap_unit(sane.data_type()));
+    }
+    writer.write_all(&data).map_err(WriteError::Failed)
+}
+
+#[cfg(test)]
+mod tests {
+    use ndarray::array;
+
+    use super::{read_raw, write_raw, Endianness};
+    use crate::read::ParseError;
+    use crate::{DataType, Sane};
+
+    #[test]
+    fn roundtrip_raw() {
+        let sane = Sane::from(array![[1u16, 2, 3], [0x0102, 0xfffe, 0]]);
+        for endianness in [Endianness::Little, Endianness::Big] {
+            let mut buffer = vec![];
+            write_raw(&mut buffer, &sane, endianness).unwrap();
+            assert_eq!(buffer.len(), 12);
+            let read = read_raw(&mut buffer.as_slice(), &[2, 3], DataType::U16, endianness).unwrap();
+            assert_eq!(read, sane);
+        }
+    }
+
+    #[test]
+    fn big_endian() {
+        let data = [0x3f, 0x80, 0, 0, 0xc0, 0, 0, 0];
+        let read = read_raw(&mut data.as_slice(), &[2], DataType::F32, Endianness::Big).unwrap();
+        assert_eq!(read, Sane::from(array![1.0f32, -2.0]));
+        let err = read_raw(&mut data.as_slice(), &[3], DataType::F32, Endianness::Big).unwrap_err();
+        assert!(matches!(err, ParseError::NotEnoughBytes(_)));
+    }
+}
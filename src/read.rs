@@ -1,83 +1,12 @@
 use std::io::{prelude::Read, ErrorKind};
 use std::num::TryFromIntError;
 
-use ndarray::{IxDyn, ArrayView, ArrayD, Array, Dimension, ShapeError};
-use crate::data::{DataType, SaneData, Sane, Header, parse_data_type};
-
-// This cannot be written as a generic function because
-// `std::mem::size_of::<T>()` cannot be called for a generic `T`,
-// despite having a `T: Sized` constraint.
-macro_rules! sane_from_le_bytes {
-    ($t:ty, $e:expr) => {
-        {
-            const COUNT: usize = std::mem::size_of::<$t>();
-            let elems = $e.len() / COUNT;
-            let mut result = vec![];
-            for i in 0..elems {
-                let elem_bytes: [u8; COUNT] = $e[i*COUNT..(i+1)*COUNT].try_into().unwrap();
-                result.push(<$t>::from_le_bytes(elem_bytes));
-            };
-            result
-        }
-    }
-}
-
-/// To be able read SANE-encoded data we need to be able convert the `Vec<u8>` of little-endian
-/// data to the corresponding vector of values
-pub trait ReadSane: SaneData {
-    fn from_le_bytes(bytes: Vec<u8>) -> Vec<Self>;
-}
-
-impl ReadSane for f32 {
-    fn from_le_bytes(bytes: Vec<u8>) -> Vec<f32> {
-        return sane_from_le_bytes!(f32, bytes);
-    }
-}
-
-impl ReadSane for i32 {
-    fn from_le_bytes(bytes: Vec<u8>) -> Vec<i32> {
-        return sane_from_le_bytes!(i32, bytes);
-    }
-}
-
-impl ReadSane for u32 {
-    fn from_le_bytes(bytes: Vec<u8>) -> Vec<u32> {
-        return sane_from_le_bytes!(u32, bytes);
-    }
-}
-
-impl ReadSane for f64 {
-    fn from_le_bytes(bytes: Vec<u8>) -> Vec<f64> {
-        return sane_from_le_bytes!(f64, bytes);
-    }
-}
-
-impl ReadSane for i64 {
-    fn from_le_bytes(bytes: Vec<u8>) -> Vec<i64> {
-        return sane_from_le_bytes!(i64, bytes);
-    }
-}
-
-impl ReadSane for u64 {
-    fn from_le_bytes(bytes: Vec<u8>) -> Vec<u64> {
-        return sane_from_le_bytes!(u64, bytes);
-    }
-}
-
-impl ReadSane for i8 {
-    fn from_le_bytes(bytes: Vec<u8>) -> Vec<i8> {
-        return sane_from_le_bytes!(i8, bytes);
-    }
-}
-
-impl ReadSane for u8 {
-    fn from_le_bytes(bytes: Vec<u8>) -> Vec<u8> {
-        return bytes;
-    }
-}
-
+use ndarray::{IxDyn, ArrayD, Array, Dimension, ShapeError, ErrorKind as ShapeErrorKind};
+use crate::data::{DataType, Sane, Header, parse_data_type};
+use crate::element::SaneElement;
 
 #[derive(Debug)]
+#[non_exhaustive]
 pub enum ParseError {
     EOF,
     NotSANE,
@@ -87,6 +16,8 @@ pub enum ParseError {
     ReadError(std::io::Error),
     ShapeError(ShapeError),
     WrongDataType(DataType),
+    UnsupportedDataType(DataType),
+    InvalidBool(u8),
 }
 
 impl std::fmt::Display for ParseError {
@@ -101,6 +32,8 @@ impl std::fmt::Display for ParseError {
             ReadError(err) => write!(f, "Failed to read: {}", err),
             ShapeError(err) => write!(f, "{}", err),
             WrongDataType(t) => write!(f, "unexpected data type {:?}", t),
+            UnsupportedDataType(t) => write!(f, "data type {:?} is not enabled in this build", t),
+            InvalidBool(byte) => write!(f, "Invalid boolean byte {}, expected 0 or 1", byte),
         }
     }
 }
@@ -151,49 +84,43 @@ fn read_header<F: Read>(file: &mut F) -> Result<Header, ParseError> {
     })
 }
 
-fn read_array<T: ReadSane>(dims: IxDyn, byte_data: Vec<u8>) -> Result<ArrayD<T>, ParseError> {
-    if cfg!(endianness = "little") {
-        // If we're on a little-endian system we can just cast the bytes to our type
-        // as the SANE spec guarantees that the data is in little-endian byte order
-        let values = unsafe {
-            byte_data.align_to::<T>().1
-        };
-        let array_view = ArrayView::from_shape(dims, &values).map_err(ParseError::ShapeError)?;
-        Ok(array_view.to_owned())
-    } else {
-        let vec = T::from_le_bytes(byte_data);
-        let array_view = ArrayView::from_shape(dims, &vec).map_err(ParseError::ShapeError)?;
-        Ok(array_view.to_owned())
+/// Decode little-endian `byte_data` into the values of an array
+fn decode_values<T: SaneElement>(byte_data: &[u8]) -> Result<Vec<T>, ParseError> {
+    if byte_data.len() % T::SIZE != 0 {
+        return Err(ParseError::ShapeError(ShapeError::from_kind(ShapeErrorKind::IncompatibleShape)));
     }
+    let mut values = vec![T::zeroed(); byte_data.len() / T::SIZE];
+    T::decode_le(byte_data, &mut values)?;
+    Ok(values)
 }
 
-fn read_array_with_shape<T: ReadSane, D: Dimension>(shape: Vec<usize>, byte_data: Vec<u8>) -> Result<Array<T,D>, ParseError> {
-    let dyn_dims = IxDyn(&shape);
-    if cfg!(endianness = "little") {
-        let values = unsafe {
-            // If we're on a little-endian system we can just cast the bytes to our type
-            // as the SANE spec guarantees that the data is in little-endian byte order
-            byte_data.align_to::<T>().1
-        };
-        let array_view = ArrayView::from_shape(dyn_dims, &values).map_err(ParseError::ShapeError)?;
-        let shaped_array = array_view.into_dimensionality().map_err(ParseError::ShapeError)?;
-        Ok(shaped_array.to_owned())
-    } else {
-        let values = T::from_le_bytes(byte_data);
-        let array_view = ArrayView::from_shape(dyn_dims, &values).map_err(ParseError::ShapeError)?;
-        let shaped_array = array_view.into_dimensionality().map_err(ParseError::ShapeError)?;
-        Ok(shaped_array.to_owned())
-    }
+fn read_array<T: SaneElement>(dims: IxDyn, byte_data: &[u8]) -> Result<ArrayD<T>, ParseError> {
+    let values = decode_values(byte_data)?;
+    Array::from_shape_vec(dims, values).map_err(ParseError::ShapeError)
+}
+
+fn read_array_with_shape<T: SaneElement, D: Dimension>(shape: Vec<usize>, byte_data: Vec<u8>) -> Result<Array<T,D>, ParseError> {
+    let array = read_array(IxDyn(&shape), &byte_data)?;
+    array.into_dimensionality().map_err(ParseError::ShapeError)
+}
+
+/// Decode little-endian element data into an array with the given shape and data type
+pub(crate) fn decode_sane(shape: &[usize], data_type: DataType, byte_data: &[u8]) -> Result<Sane, ParseError> {
+    crate::sane_dispatch!(
+        DataType: data_type,
+        A => read_array::<A>(IxDyn(shape), byte_data).map(Sane::from),
+        _ => Err(ParseError::UnsupportedDataType(data_type))
+    )
 }
 
 /// Parse a SANE-encoded file into an array with known type and rank
-pub fn read_sane<F: Read, A: ReadSane, D: Dimension>(
+pub fn read_sane<F: Read, A: SaneElement, D: Dimension>(
     file: &mut F,
 ) -> Result<Array<A, D>, ParseError> {
     let header = read_header(file)?;
     let mut sane_data = vec![0u8; header.data_length];
     file.read_exact(&mut sane_data).map_err(ParseError::NotEnoughBytes)?;
-    if header.data_type != A::sane_data_type() {
+    if header.data_type != A::DATA_TYPE {
         Err(ParseError::WrongDataType(header.data_type))?;
     }
     let sane = read_array_with_shape(header.shape, sane_data)?;
@@ -208,22 +135,11 @@ pub fn read_sane_dyn<F: Read>(
     let header = read_header(file)?;
     let mut sane_data = vec![0u8; header.data_length];
     file.read_exact(&mut sane_data).map_err(ParseError::NotEnoughBytes)?;
-    let dims: IxDyn = IxDyn(&header.shape);
-    let sane = match header.data_type {
-        DataType::F32 => read_array(dims, sane_data).map(Sane::ArrayF32),
-        DataType::I32 => read_array(dims, sane_data).map(Sane::ArrayI32),
-        DataType::U32 => read_array(dims, sane_data).map(Sane::ArrayU32),
-        DataType::F64 => read_array(dims, sane_data).map(Sane::ArrayF64),
-        DataType::I64 => read_array(dims, sane_data).map(Sane::ArrayI64),
-        DataType::U64 => read_array(dims, sane_data).map(Sane::ArrayU64),
-        DataType::I8 => read_array(dims, sane_data).map(Sane::ArrayI8),
-        DataType::U8 => read_array(dims, sane_data).map(Sane::ArrayU8),
-    }?;
-    Ok(sane)
+    decode_sane(&header.shape, header.data_type, &sane_data)
 }
 
 /// Parse multiple SANE-encoded arrays from a file
-pub fn read_sane_arrays<F: Read, A: ReadSane, D: Dimension>(
+pub fn read_sane_arrays<F: Read, A: SaneElement, D: Dimension>(
     file: &mut F,
 ) -> Result<Vec<Array<A, D>>, ParseError> {
     let mut arrays = vec![];
@@ -0,0 +1,202 @@
+//! Reading and writing [safetensors](https://huggingface.co/docs/safetensors) files of named
+//! arrays
+//!
+//! A safetensors file is a JSON header describing the data type, shape and byte offsets of each
+//! tensor followed by the little-endian tensor data. `bool`, integer, `f16`, `bf16`, `f32`, `f64`
+//! and `c64` tensors have SANE equivalents. `f16` and `bf16` tensors need the `half` feature and
+//! `c64` tensors the `num-complex` feature.
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::io::{Read, Write};
+
+use ::safetensors::{Dtype, SafeTensorError, SafeTensors, View};
+
+use crate::data::{DataType, Sane, SaneView};
+use crate::read::{decode_sane, ParseError};
+use crate::write::data_bytes;
+
+#[derive(Debug)]
+pub enum SafetensorsError {
+    Io(std::io::Error),
+    Safetensors(SafeTensorError),
+    /// A tensor with a data type that SANE does not support
+    UnsupportedDtype(String, Dtype),
+    /// An array with a data type that safetensors does not support
+    UnsupportedDataType(String, DataType),
+    Parse(String, ParseError),
+}
+
+impl std::fmt::Display for SafetensorsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        use SafetensorsError::*;
+        match self {
+            Io(err) => write!(f, "I/O error: {}", err),
+            Safetensors(err) => write!(f, "Invalid safetensors file: {}", err),
+            UnsupportedDtype(name, dtype) => write!(f, "Tensor {} has unsupported dtype {}", name, dtype),
+            UnsupportedDataType(name, data_type) => write!(f, "Array {}: data type {} has no safetensors equivalent", name, data_type),
+            Parse(name, err) => write!(f, "Tensor {}: {}", name, err),
+        }
+    }
+}
+
+impl std::error::Error for SafetensorsError {}
+
+/// The safetensors dtype of a data type, if there is one
+pub fn safetensors_dtype(data_type: DataType) -> Option<Dtype> {
+    Some(match data_type {
+        DataType::F32 => Dtype::F32,
+        DataType::I32 => Dtype::I32,
+        DataType::U32 => Dtype::U32,
+        DataType::F64 => Dtype::F64,
+        DataType::I64 => Dtype::I64,
+        DataType::U64 => Dtype::U64,
+        DataType::I8 => Dtype::I8,
+        DataType::U8 => Dtype::U8,
+        DataType::I16 => Dtype::I16,
+        DataType::U16 => Dtype::U16,
+        DataType::F16 => Dtype::F16,
+        DataType::BF16 => Dtype::BF16,
+        DataType::C64 => Dtype::C64,
+        DataType::Bool => Dtype::BOOL,
+        DataType::C128 => return None,
+    })
+}
+
+/// The data type of a safetensors dtype, if there is one
+pub fn from_safetensors_dtype(dtype: Dtype) -> Option<DataType> {
+    Some(match dtype {
+        Dtype::F32 => DataType::F32,
+        Dtype::I32 => DataType::I32,
+        Dtype::U32 => DataType::U32,
+        Dtype::F64 => DataType::F64,
+        Dtype::I64 => DataType::I64,
+        Dtype::U64 => DataType::U64,
+        Dtype::I8 => DataType::I8,
+        Dtype::U8 => DataType::U8,
+        Dtype::I16 => DataType::I16,
+        Dtype::U16 => DataType::U16,
+        Dtype::F16 => DataType::F16,
+        Dtype::BF16 => DataType::BF16,
+        Dtype::C64 => DataType::C64,
+        Dtype::BOOL => DataType::Bool,
+        _ => return None,
+    })
+}
+
+/// The little-endian data of an array, as serialized by safetensors
+struct Tensor<'a> {
+    dtype: Dtype,
+    shape: &'a [usize],
+    data: Cow<'a, [u8]>,
+}
+
+impl View for &Tensor<'_> {
+    fn dtype(&self) -> Dtype {
+        self.dtype
+    }
+
+    fn shape(&self) -> &[usize] {
+        self.shape
+    }
+
+    fn data(&self) -> Cow<'_, [u8]> {
+        Cow::Borrowed(&self.data)
+    }
+
+    fn data_len(&self) -> usize {
+        self.data.len()
+    }
+}
+
+/// Read all tensors of a safetensors file, keyed by their names
+///
+/// The whole file is read into memory first.
+pub fn read_safetensors<R: Read>(reader: &mut R) -> Result<BTreeMap<String, Sane>, SafetensorsError> {
+    let mut buffer = vec![];
+    reader.read_to_end(&mut buffer).map_err(SafetensorsError::Io)?;
+    let tensors = SafeTensors::deserialize(&buffer).map_err(SafetensorsError::Safetensors)?;
+    let mut arrays = BTreeMap::new();
+    for (name, tensor) in tensors.iter() {
+        let data_type = from_safetensors_dtype(tensor.dtype())
+            .ok_or_else(|| SafetensorsError::UnsupportedDtype(name.to_string(), tensor.dtype()))?;
+        let sane = decode_sane(tensor.shape(), data_type, tensor.data())
+            .map_err(|err| SafetensorsError::Parse(name.to_string(), err))?;
+        arrays.insert(name.to_string(), sane);
+    }
+    Ok(arrays)
+}
+
+/// Write named arrays to a safetensors file, with optional free-form metadata in its header
+pub fn write_safetensors<'a, W, Arrays, K, S>(
+    writer: &mut W,
+    arrays: Arrays,
+    metadata: Option<&BTreeMap<String, String>>,
+) -> Result<(), SafetensorsError>
+where
+    W: Write,
+    Arrays: IntoIterator<Item = (K, S)>,
+    K: AsRef<str>,
+    S: Into<SaneView<'a>>
+{
+    let views: Vec<(String, SaneView<'a>)> = arrays.into_iter()
+        .map(|(name, sane)| (name.as_ref().to_string(), sane.into()))
+        .collect();
+    let mut tensors = Vec::with_capacity(views.len());
+    for (name, sane) in &views {
+        let dtype = safetensors_dtype(sane.data_type())
+            .ok_or_else(|| SafetensorsError::UnsupportedDataType(name.clone(), sane.data_type()))?;
+        let data = crate::sane_dispatch!(SaneView: sane, array => data_bytes(array));
+        tensors.push((name.as_str(), Tensor { dtype, shape: sane.shape(), data }));
+    }
+    let metadata = metadata.map(|metadata| metadata.iter().map(|(key, value)| (key.clone(), value.clone())).collect());
+    let bytes = ::safetensors::serialize(tensors.iter().map(|(name, tensor)| (*name, tensor)), metadata)
+        .map_err(SafetensorsError::Safetensors)?;
+    writer.write_all(&bytes).map_err(SafetensorsError::Io)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use ndarray::{array, Array};
+
+    use super::{read_safetensors, write_safetensors, SafetensorsError};
+    use crate::Sane;
+
+    #[test]
+    fn roundtrip_safetensors() {
+        let mut arrays = BTreeMap::new();
+        arrays.insert("weights".to_string(), Sane::from(Array::linspace(0.0f32, 1.0, 12).into_shape((3, 4)).unwrap()));
+        arrays.insert("bias".to_string(), Sane::from(array![-1i64, 2, -3]));
+        arrays.insert("mask".to_string(), Sane::from(array![[true, false], [false, true]]));
+        arrays.insert("scalar".to_string(), Sane::from(ndarray::arr0(7u16).into_dyn()));
+        let mut metadata = BTreeMap::new();
+        metadata.insert("format".to_string(), "pt".to_string());
+        let mut buffer = vec![];
+        write_safetensors(&mut buffer, &arrays, Some(&metadata)).unwrap();
+        assert_eq!(read_safetensors(&mut buffer.as_slice()).unwrap(), arrays);
+    }
+
+    #[test]
+    fn unsupported_dtype() {
+        let header = br#"{"x":{"dtype":"F8_E4M3","shape":[2],"data_offsets":[0,2]}}"#;
+        let mut file = (header.len() as u64).to_le_bytes().to_vec();
+        file.extend_from_slice(header);
+        file.extend_from_slice(&[0, 0]);
+        let err = read_safetensors(&mut file.as_slice()).unwrap_err();
+        assert!(matches!(err, SafetensorsError::UnsupportedDtype(name, _) if name == "x"));
+    }
+
+    #[cfg(feature = "num-complex")]
+    #[test]
+    fn complex() {
+        use num_complex::Complex;
+        let c64 = Sane::from(array![Complex::new(1.0f32, 2.0)]);
+        let mut buffer = vec![];
+        write_safetensors(&mut buffer, [("c64", &c64)], None).unwrap();
+        assert_eq!(read_safetensors(&mut buffer.as_slice()).unwrap()["c64"], c64);
+        let c128 = Sane::from(array![Complex::new(1.0f64, 2.0)]);
+        let err = write_safetensors(&mut vec![], [("c128", &c128)], None).unwrap_err();
+        assert!(matches!(err, SafetensorsError::UnsupportedDataType(_, crate::DataType::C128)));
+    }
+}
@@ -0,0 +1,472 @@
+//! [Serde](https://serde.rs) support for [`Sane`] arrays and [`DataType`]s
+//!
+//! Human-readable formats such as JSON represent an array as `{dtype, shape, data}` with the
+//! elements of `data` flattened in row-major order. Other formats store the SANE encoding of the
+//! array as bytes.
+//!
+//! Since JSON has no NaN or infinity, non-finite floating point elements are written as the
+//! strings `"NaN"`, `"Infinity"` and `"-Infinity"`, the same as in the `json` module. `null` is
+//! read back as NaN.
+//!
+//! Typed array fields can be serialized the same way with `#[serde(with = "sane_array::serde")]`:
+//!
+//! ```
+//! use ndarray::{array, Array2};
+//! use serde::{Deserialize, Serialize};
+//!
+//! #[derive(Serialize, Deserialize)]
+//! struct Image {
+//!     #[serde(with = "sane_array::serde")]
+//!     pixels: Array2<u8>,
+//! }
+//!
+//! let image = Image { pixels: array![[0, 255], [255, 0]] };
+//! let json = serde_json::to_string(&image).unwrap();
+//! assert_eq!(json, r#"{"pixels":{"dtype":"u8","shape":[2,2],"data":[0,255,255,0]}}"#);
+//! let decoded: Image = serde_json::from_str(&json).unwrap();
+//! assert_eq!(decoded.pixels, image.pixels);
+//! ```
+use std::fmt;
+use std::io::Cursor;
+
+use ::serde::de::{self, Deserializer, MapAccess, SeqAccess, Visitor};
+use ::serde::ser::{SerializeStruct, Serializer};
+use ::serde::{Deserialize, Serialize};
+use ndarray::{Array, ArrayBase, ArrayD, ArrayViewD, Data, Dimension};
+
+use crate::data::{DataType, Sane, SaneView};
+use crate::element::{non_finite_name, parse_non_finite, SaneElement};
+use crate::read::read_sane_dyn;
+use crate::write::write_sane_dyn;
+
+const FIELDS: &[&str] = &["dtype", "shape", "data"];
+
+impl Serialize for DataType {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            serializer.serialize_str(self.name())
+        } else {
+            serializer.serialize_u8(u8::from(*self))
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for DataType {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        if deserializer.is_human_readable() {
+            let name = <std::borrow::Cow<str>>::deserialize(deserializer)?;
+            name.parse().map_err(de::Error::custom)
+        } else {
+            let code = u8::deserialize(deserializer)?;
+            DataType::try_from(code)
+                .map_err(|code| de::Error::custom(format!("invalid data type code {}", code)))
+        }
+    }
+}
+
+/// A single element as found in the `data` of a human-readable array, before the data type is
+/// known
+#[derive(Debug, Clone, Copy)]
+enum Scalar {
+    Bool(bool),
+    U64(u64),
+    I64(i64),
+    F64(f64),
+    /// Read as NaN, since formats like JSON write `null` for non-finite numbers
+    Null,
+    /// The real and imaginary parts of a complex number
+    #[cfg_attr(not(feature = "num-complex"), allow(dead_code))]
+    Pair(f64, f64),
+}
+
+impl<'de> Deserialize<'de> for Scalar {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct ScalarVisitor;
+
+        impl<'de> Visitor<'de> for ScalarVisitor {
+            type Value = Scalar;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "a number, a boolean or a pair of numbers")
+            }
+
+            fn visit_str<E: de::Error>(self, value: &str) -> Result<Scalar, E> {
+                parse_non_finite(value)
+                    .map(Scalar::F64)
+                    .ok_or_else(|| E::invalid_value(de::Unexpected::Str(value), &self))
+            }
+
+            fn visit_bool<E>(self, value: bool) -> Result<Scalar, E> {
+                Ok(Scalar::Bool(value))
+            }
+
+            fn visit_u64<E>(self, value: u64) -> Result<Scalar, E> {
+                Ok(Scalar::U64(value))
+            }
+
+            fn visit_i64<E>(self, value: i64) -> Result<Scalar, E> {
+                Ok(Scalar::I64(value))
+            }
+
+            fn visit_f64<E>(self, value: f64) -> Result<Scalar, E> {
+                Ok(Scalar::F64(value))
+            }
+
+            fn visit_unit<E>(self) -> Result<Scalar, E> {
+                Ok(Scalar::Null)
+            }
+
+            fn visit_none<E>(self) -> Result<Scalar, E> {
+                Ok(Scalar::Null)
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Scalar, A::Error> {
+                let mut part = |index| seq.next_element::<Scalar>()?
+                    .ok_or_else(|| de::Error::invalid_length(index, &self))?
+                    .as_f64()
+                    .ok_or_else(|| de::Error::custom("invalid part of a complex number"));
+                let re = part(0)?;
+                let im = part(1)?;
+                Ok(Scalar::Pair(re, im))
+            }
+        }
+
+        deserializer.deserialize_any(ScalarVisitor)
+    }
+}
+
+impl Scalar {
+    fn as_f64(self) -> Option<f64> {
+        match self {
+            Scalar::U64(value) => Some(value as f64),
+            Scalar::I64(value) => Some(value as f64),
+            Scalar::F64(value) => Some(value),
+            Scalar::Null => Some(f64::NAN),
+            _ => None,
+        }
+    }
+}
+
+/// How each element type is represented in human-readable formats
+trait SerdeElement: SaneElement {
+    fn serialize_element<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error>;
+
+    fn from_scalar(scalar: Scalar) -> Option<Self>;
+}
+
+macro_rules! integer_element {
+    ($($t:ty),*) => {
+        $(impl SerdeElement for $t {
+            fn serialize_element<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                self.serialize(serializer)
+            }
+
+            fn from_scalar(scalar: Scalar) -> Option<Self> {
+                match scalar {
+                    Scalar::U64(value) => <$t>::try_from(value).ok(),
+                    Scalar::I64(value) => <$t>::try_from(value).ok(),
+                    _ => None,
+                }
+            }
+        })*
+    }
+}
+
+integer_element!(i32, u32, i64, u64, i8, u8, i16, u16);
+
+impl SerdeElement for f32 {
+    fn serialize_element<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match non_finite_name(*self as f64) {
+            Some(name) => serializer.serialize_str(name),
+            None => serializer.serialize_f32(*self),
+        }
+    }
+
+    fn from_scalar(scalar: Scalar) -> Option<Self> {
+        scalar.as_f64().map(|value| value as f32)
+    }
+}
+
+impl SerdeElement for f64 {
+    fn serialize_element<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match non_finite_name(*self) {
+            Some(name) => serializer.serialize_str(name),
+            None => serializer.serialize_f64(*self),
+        }
+    }
+
+    fn from_scalar(scalar: Scalar) -> Option<Self> {
+        scalar.as_f64()
+    }
+}
+
+impl SerdeElement for bool {
+    fn serialize_element<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_bool(*self)
+    }
+
+    fn from_scalar(scalar: Scalar) -> Option<Self> {
+        match scalar {
+            Scalar::Bool(value) => Some(value),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(feature = "half")]
+impl SerdeElement for half::f16 {
+    fn serialize_element<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.to_f32().serialize_element(serializer)
+    }
+
+    fn from_scalar(scalar: Scalar) -> Option<Self> {
+        scalar.as_f64().map(half::f16::from_f64)
+    }
+}
+
+#[cfg(feature = "half")]
+impl SerdeElement for half::bf16 {
+    fn serialize_element<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.to_f32().serialize_element(serializer)
+    }
+
+    fn from_scalar(scalar: Scalar) -> Option<Self> {
+        scalar.as_f64().map(half::bf16::from_f64)
+    }
+}
+
+/// Complex numbers are represented as `[re, im]` pairs
+#[cfg(feature = "num-complex")]
+impl SerdeElement for num_complex::Complex<f32> {
+    fn serialize_element<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        (Element(self.re), Element(self.im)).serialize(serializer)
+    }
+
+    fn from_scalar(scalar: Scalar) -> Option<Self> {
+        match scalar {
+            Scalar::Pair(re, im) => Some(num_complex::Complex::new(re as f32, im as f32)),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(feature = "num-complex")]
+impl SerdeElement for num_complex::Complex<f64> {
+    fn serialize_element<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        (Element(self.re), Element(self.im)).serialize(serializer)
+    }
+
+    fn from_scalar(scalar: Scalar) -> Option<Self> {
+        match scalar {
+            Scalar::Pair(re, im) => Some(num_complex::Complex::new(re, im)),
+            _ => None,
+        }
+    }
+}
+
+struct Element<A>(A);
+
+impl<A: SerdeElement> Serialize for Element<A> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.0.serialize_element(serializer)
+    }
+}
+
+/// The elements of an array in row-major order
+struct Elements<'a, 'b, A>(&'b ArrayViewD<'a, A>);
+
+impl<'a, 'b, A: SerdeElement> Serialize for Elements<'a, 'b, A> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.0.iter().map(|&elem| Element(elem)))
+    }
+}
+
+impl<'a> Serialize for SaneView<'a> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            let mut state = serializer.serialize_struct("Sane", FIELDS.len())?;
+            state.serialize_field("dtype", &self.data_type())?;
+            state.serialize_field("shape", self.shape())?;
+            crate::sane_dispatch!(SaneView: self, array => state.serialize_field("data", &Elements(array)))?;
+            state.end()
+        } else {
+            let mut bytes = Vec::with_capacity(self.encoded_len());
+            write_sane_dyn(&mut bytes, self.clone()).map_err(::serde::ser::Error::custom)?;
+            serializer.serialize_bytes(&bytes)
+        }
+    }
+}
+
+impl Serialize for Sane {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.view().serialize(serializer)
+    }
+}
+
+/// Build an array from the fields of its human-readable representation
+fn from_parts<E: de::Error>(data_type: DataType, shape: Vec<usize>, data: Vec<Scalar>) -> Result<Sane, E> {
+    crate::sane_dispatch!(
+        DataType: data_type,
+        A => {
+            let values = data.into_iter()
+                .map(|scalar| A::from_scalar(scalar)
+                    .ok_or_else(|| E::custom(format!("invalid {} element {:?}", data_type, scalar))))
+                .collect::<Result<Vec<A>, E>>()?;
+            let array = ArrayD::from_shape_vec(shape, values).map_err(E::custom)?;
+            Ok(Sane::from(array))
+        },
+        _ => Err(E::custom(format!("data type {} is not enabled in this build", data_type)))
+    )
+}
+
+struct SaneVisitor;
+
+impl<'de> Visitor<'de> for SaneVisitor {
+    type Value = Sane;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "a SANE array")
+    }
+
+    fn visit_bytes<E: de::Error>(self, bytes: &[u8]) -> Result<Sane, E> {
+        read_sane_dyn(&mut Cursor::new(bytes)).map_err(E::custom)
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Sane, A::Error> {
+        let mut bytes = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+        while let Some(byte) = seq.next_element::<u8>()? {
+            bytes.push(byte);
+        }
+        self.visit_bytes(&bytes)
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Sane, A::Error> {
+        let mut data_type = None;
+        let mut shape = None;
+        let mut data = None;
+        while let Some(key) = map.next_key::<std::borrow::Cow<str>>()? {
+            match &*key {
+                "dtype" => data_type = Some(map.next_value::<DataType>()?),
+                "shape" => shape = Some(map.next_value::<Vec<usize>>()?),
+                "data" => data = Some(map.next_value::<Vec<Scalar>>()?),
+                other => return Err(de::Error::unknown_field(other, FIELDS)),
+            }
+        }
+        let data_type = data_type.ok_or_else(|| de::Error::missing_field("dtype"))?;
+        let shape = shape.ok_or_else(|| de::Error::missing_field("shape"))?;
+        let data = data.ok_or_else(|| de::Error::missing_field("data"))?;
+        from_parts(data_type, shape, data)
+    }
+}
+
+impl<'de> Deserialize<'de> for Sane {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        if deserializer.is_human_readable() {
+            deserializer.deserialize_struct("Sane", FIELDS, SaneVisitor)
+        } else {
+            deserializer.deserialize_bytes(SaneVisitor)
+        }
+    }
+}
+
+/// Serialize a typed array like a [`Sane`] array, for use with `#[serde(with = "sane_array::serde")]`
+pub fn serialize<A, D, S, Ser>(array: &ArrayBase<S, D>, serializer: Ser) -> Result<Ser::Ok, Ser::Error>
+where
+    A: SaneElement,
+    D: Dimension,
+    S: Data<Elem = A>,
+    Ser: Serializer,
+{
+    SaneView::from(array.view()).serialize(serializer)
+}
+
+/// Deserialize a typed array from a [`Sane`] array, for use with
+/// `#[serde(with = "sane_array::serde")]`
+pub fn deserialize<'de, A, D, De>(deserializer: De) -> Result<Array<A, D>, De::Error>
+where
+    A: SaneElement,
+    D: Dimension,
+    De: Deserializer<'de>,
+{
+    let sane = Sane::deserialize(deserializer)?;
+    Array::try_from(sane).map_err(de::Error::custom)
+}
+
+#[cfg(test)]
+mod tests {
+    use ndarray::{array, Array2, ArrayD, IxDyn};
+    use serde::{Deserialize, Serialize};
+
+    use crate::{DataType, Sane};
+
+    #[test]
+    fn json_roundtrip() {
+        let sane = Sane::from(array![[1.5f32, -2.0], [f32::NAN, 4.0]]);
+        let json = serde_json::to_string(&sane).unwrap();
+        assert_eq!(json, r#"{"dtype":"f32","shape":[2,2],"data":[1.5,-2.0,"NaN",4.0]}"#);
+        let decoded: Sane = serde_json::from_str(&json).unwrap();
+        let decoded: Array2<f32> = decoded.try_into().unwrap();
+        assert!(decoded[[1, 0]].is_nan());
+        assert_eq!(decoded[[0, 1]], -2.0);
+
+        let sane = Sane::from(ArrayD::from_shape_vec(IxDyn(&[3]), vec![true, false, true]).unwrap());
+        let decoded: Sane = serde_json::from_str(&serde_json::to_string(&sane).unwrap()).unwrap();
+        assert_eq!(decoded, sane);
+    }
+
+    #[test]
+    fn json_non_finite() {
+        let sane = Sane::from(array![f64::INFINITY, f64::NEG_INFINITY, 0.5]);
+        let json = serde_json::to_string(&sane).unwrap();
+        assert_eq!(json, r#"{"dtype":"f64","shape":[3],"data":["Infinity","-Infinity",0.5]}"#);
+        assert_eq!(serde_json::from_str::<Sane>(&json).unwrap(), sane);
+
+        let null: Sane = serde_json::from_str(r#"{"dtype":"f32","shape":[1],"data":[null]}"#).unwrap();
+        let null: ndarray::Array1<f32> = null.try_into().unwrap();
+        assert!(null[0].is_nan());
+        let invalid = r#"{"dtype":"f32","shape":[1],"data":["inf"]}"#;
+        assert!(serde_json::from_str::<Sane>(invalid).is_err());
+    }
+
+    #[cfg(feature = "num-complex")]
+    #[test]
+    fn json_non_finite_complex() {
+        let sane = Sane::from(array![num_complex::Complex::new(f32::INFINITY, -1.0)]);
+        let json = serde_json::to_string(&sane).unwrap();
+        assert_eq!(json, r#"{"dtype":"c64","shape":[1],"data":[["Infinity",-1.0]]}"#);
+        assert_eq!(serde_json::from_str::<Sane>(&json).unwrap(), sane);
+    }
+
+    #[test]
+    fn json_rejects_invalid() {
+        let wrong_len = r#"{"dtype":"i8","shape":[2],"data":[1]}"#;
+        assert!(serde_json::from_str::<Sane>(wrong_len).is_err());
+        let out_of_range = r#"{"dtype":"u8","shape":[1],"data":[256]}"#;
+        assert!(serde_json::from_str::<Sane>(out_of_range).is_err());
+    }
+
+    #[test]
+    fn bincode_roundtrip() {
+        let sane = Sane::from(array![[1u64, 2, 3], [4, 5, 6]]);
+        let bytes = bincode::serialize(&sane).unwrap();
+        let decoded: Sane = bincode::deserialize(&bytes).unwrap();
+        assert_eq!(decoded, sane);
+
+        let data_type: DataType = bincode::deserialize(&bincode::serialize(&DataType::I16).unwrap()).unwrap();
+        assert_eq!(data_type, DataType::I16);
+    }
+
+    #[test]
+    fn typed_field() {
+        #[derive(Serialize, Deserialize)]
+        struct Weights {
+            #[serde(with = "crate::serde")]
+            values: Array2<i32>,
+        }
+
+        let weights = Weights { values: array![[1, 2], [3, 4]].reversed_axes() };
+        let bytes = bincode::serialize(&weights).unwrap();
+        let decoded: Weights = bincode::deserialize(&bytes).unwrap();
+        assert_eq!(decoded.values, weights.values);
+    }
+}
@@ -0,0 +1,106 @@
+//! [proptest](https://docs.rs/proptest) strategies for generating random SANE arrays
+use std::fmt::Debug;
+
+use ndarray::ArrayD;
+use proptest::prelude::*;
+
+use crate::data::{DataType, Sane};
+use crate::element::SaneElement;
+
+/// Element types with a strategy for generating their values
+pub trait ElementStrategy: SaneElement + Debug {
+    fn element_strategy() -> BoxedStrategy<Self>;
+}
+
+macro_rules! element_strategy {
+    ($($t:ty),*) => {
+        $(impl ElementStrategy for $t {
+            fn element_strategy() -> BoxedStrategy<Self> {
+                any::<$t>().boxed()
+            }
+        })*
+    }
+}
+
+element_strategy!(f32, i32, u32, f64, i64, u64, i8, u8, i16, u16, bool);
+
+#[cfg(feature = "half")]
+impl ElementStrategy for half::f16 {
+    fn element_strategy() -> BoxedStrategy<Self> {
+        any::<f32>().prop_map(half::f16::from_f32).boxed()
+    }
+}
+
+#[cfg(feature = "half")]
+impl ElementStrategy for half::bf16 {
+    fn element_strategy() -> BoxedStrategy<Self> {
+        any::<f32>().prop_map(half::bf16::from_f32).boxed()
+    }
+}
+
+#[cfg(feature = "num-complex")]
+impl ElementStrategy for num_complex::Complex<f32> {
+    fn element_strategy() -> BoxedStrategy<Self> {
+        (any::<f32>(), any::<f32>()).prop_map(|(re, im)| num_complex::Complex::new(re, im)).boxed()
+    }
+}
+
+#[cfg(feature = "num-complex")]
+impl ElementStrategy for num_complex::Complex<f64> {
+    fn element_strategy() -> BoxedStrategy<Self> {
+        (any::<f64>(), any::<f64>()).prop_map(|(re, im)| num_complex::Complex::new(re, im)).boxed()
+    }
+}
+
+/// The data types whose element types are enabled in this build
+pub fn data_type() -> impl Strategy<Value = DataType> {
+    let enabled: Vec<DataType> = DataType::ALL
+        .iter()
+        .filter(|data_type| crate::sane_dispatch!(DataType: data_type, A => true, _ => false))
+        .copied()
+        .collect();
+    proptest::sample::select(enabled)
+}
+
+/// Shapes with at most `max_ndim` dimensions, each at most `max_axis_len` long
+pub fn shape(max_ndim: usize, max_axis_len: usize) -> impl Strategy<Value = Vec<usize>> {
+    proptest::collection::vec(0..=max_axis_len, 0..=max_ndim)
+}
+
+/// Arrays with elements of type `A` and shapes as generated by [`shape`]
+pub fn array<A: ElementStrategy>(max_ndim: usize, max_axis_len: usize) -> impl Strategy<Value = ArrayD<A>> {
+    shape(max_ndim, max_axis_len).prop_flat_map(|shape| {
+        let len = shape.iter().product::<usize>();
+        proptest::collection::vec(A::element_strategy(), len)
+            .prop_map(move |values| ArrayD::from_shape_vec(shape.clone(), values).unwrap())
+    })
+}
+
+/// Arrays of any enabled data type with shapes as generated by [`shape`]
+pub fn sane(max_ndim: usize, max_axis_len: usize) -> impl Strategy<Value = Sane> {
+    data_type().prop_flat_map(move |data_type| {
+        crate::sane_dispatch!(
+            DataType: data_type,
+            A => array::<A>(max_ndim, max_axis_len).prop_map(Sane::from).boxed(),
+            _ => unreachable!("only enabled data types are generated")
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+    use std::io::Cursor;
+
+    use crate::{read_sane_dyn, write_sane_dyn};
+
+    proptest! {
+        #[test]
+        fn prop_roundtrip(sane in super::sane(4, 5)) {
+            let mut file = Vec::new();
+            write_sane_dyn(&mut file, &sane).unwrap();
+            let parsed = read_sane_dyn(&mut Cursor::new(file)).unwrap();
+            prop_assert_eq!(parsed, sane);
+        }
+    }
+}
@@ -0,0 +1,398 @@
+//! Conversion between [`Sane`] arrays and delimited text such as CSV and TSV
+//!
+//! One-dimensional arrays are written as a single column and two-dimensional arrays as one line
+//! per row. Arrays of higher rank are written with one line per index of all but the last axis,
+//! starting with an index column such as `0:2` that holds those indices separated by `:`.
+//!
+//! Text does not record the data type, which is given when reading. When reading, a single
+//! column is read as a one-dimensional array, and an index column as an array of higher rank, so
+//! zero-dimensional arrays and arrays with a single column or an empty axis do not round trip
+//! exactly. Complex numbers are not supported.
+use std::fmt::Write as _;
+use std::io::{BufRead, Write};
+
+use ndarray::{ArrayD, ArrayViewD, Axis, IxDyn, ShapeError};
+
+use crate::data::{DataType, Sane, SaneView};
+use crate::element::SaneElement;
+
+/// Separates the indices in the index column of arrays of rank three and up
+const INDEX_SEPARATOR: char = ':';
+
+/// How arrays are formatted as text
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TextOptions {
+    /// Separates the values on a line, and must not be `:`, a sign, a decimal point or
+    /// alphanumeric
+    pub delimiter: char,
+    /// Number of digits after the decimal point of floating point values, or `None` for the
+    /// shortest representation that reads back as the same value
+    pub precision: Option<usize>,
+}
+
+impl TextOptions {
+    /// Comma-separated values
+    pub const CSV: TextOptions = TextOptions { delimiter: ',', precision: None };
+
+    /// Tab-separated values
+    pub const TSV: TextOptions = TextOptions { delimiter: '\t', precision: None };
+}
+
+impl Default for TextOptions {
+    fn default() -> Self {
+        TextOptions::CSV
+    }
+}
+
+#[derive(Debug)]
+pub enum TextError {
+    Io(std::io::Error),
+    UnsupportedDataType(DataType),
+    /// A delimiter that cannot be told apart from the values or the index column
+    InvalidDelimiter(char),
+    /// A value that cannot be parsed as the data type, with its line and column numbers
+    InvalidValue(usize, usize, String),
+    /// A line with a different number of values than the first line, with its line number
+    RaggedLine(usize),
+    /// An index column that is missing, malformed or out of order, with its line number
+    InvalidIndex(usize),
+    ShapeError(ShapeError),
+}
+
+impl std::fmt::Display for TextError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        use TextError::*;
+        match self {
+            Io(err) => write!(f, "I/O error: {}", err),
+            UnsupportedDataType(data_type) => write!(f, "Data type {} cannot be converted to text", data_type),
+            InvalidDelimiter(delimiter) => write!(f, "{:?} cannot be used as a delimiter", delimiter),
+            InvalidValue(line, column, value) => write!(f, "Invalid value {:?} at line {}, column {}", value, line, column),
+            RaggedLine(line) => write!(f, "Line {} has a different number of values than the first line", line),
+            InvalidIndex(line) => write!(f, "Invalid index column at line {}", line),
+            ShapeError(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for TextError {}
+
+/// Formatting and parsing of single elements
+trait TextElement: SaneElement {
+    fn format(&self, precision: Option<usize>, out: &mut String) -> Result<(), TextError>;
+
+    fn parse(text: &str) -> Option<Self>;
+}
+
+macro_rules! integer_element {
+    ($($t:ty),*) => {
+        $(impl TextElement for $t {
+            fn format(&self, _precision: Option<usize>, out: &mut String) -> Result<(), TextError> {
+                write!(out, "{}", self).unwrap();
+                Ok(())
+            }
+
+            fn parse(text: &str) -> Option<Self> {
+                text.parse().ok()
+            }
+        })*
+    }
+}
+
+integer_element!(i32, u32, i64, u64, i8, u8, i16, u16);
+
+fn format_float(value: impl std::fmt::Display, precision: Option<usize>, out: &mut String) {
+    match precision {
+        Some(precision) => write!(out, "{:.*}", precision, value).unwrap(),
+        None => write!(out, "{}", value).unwrap(),
+    }
+}
+
+// Floating point types, converted through `$via` if they have no parsing of their own
+macro_rules! float_element {
+    ($t:ty, $via:ty, $from:expr) => {
+        impl TextElement for $t {
+            fn format(&self, precision: Option<usize>, out: &mut String) -> Result<(), TextError> {
+                format_float(self, precision, out);
+                Ok(())
+            }
+
+            fn parse(text: &str) -> Option<Self> {
+                text.parse::<$via>().ok().map($from)
+            }
+        }
+    }
+}
+
+float_element!(f32, f32, |value| value);
+float_element!(f64, f64, |value| value);
+#[cfg(feature = "half")]
+float_element!(half::f16, f32, half::f16::from_f32);
+#[cfg(feature = "half")]
+float_element!(half::bf16, f32, half::bf16::from_f32);
+
+impl TextElement for bool {
+    fn format(&self, _precision: Option<usize>, out: &mut String) -> Result<(), TextError> {
+        write!(out, "{}", self).unwrap();
+        Ok(())
+    }
+
+    /// Spreadsheets tend to write booleans as numbers, so `1` and `0` are accepted as well
+    fn parse(text: &str) -> Option<Self> {
+        match text {
+            "true" | "1" => Some(true),
+            "false" | "0" => Some(false),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(feature = "num-complex")]
+impl<T: Copy> TextElement for num_complex::Complex<T> where Self: SaneElement {
+    fn format(&self, _precision: Option<usize>, _out: &mut String) -> Result<(), TextError> {
+        Err(TextError::UnsupportedDataType(Self::DATA_TYPE))
+    }
+
+    fn parse(_text: &str) -> Option<Self> {
+        None
+    }
+}
+
+fn supports_text(data_type: DataType) -> bool {
+    !data_type.is_complex()
+}
+
+/// Reject delimiters that occur in values, such as digits, signs, decimal points and the letters
+/// of `true` or `NaN`, or in the index column
+fn check_delimiter(options: &TextOptions) -> Result<(), TextError> {
+    match options.delimiter {
+        delimiter @ (INDEX_SEPARATOR | '-' | '+' | '.' | '\n' | '\r') => Err(TextError::InvalidDelimiter(delimiter)),
+        delimiter if delimiter.is_alphanumeric() => Err(TextError::InvalidDelimiter(delimiter)),
+        _ => Ok(()),
+    }
+}
+
+fn write_lines<W: Write, A: TextElement>(writer: &mut W, array: ArrayViewD<'_, A>, options: &TextOptions) -> Result<(), TextError> {
+    let mut line = String::new();
+    let mut write_line = |index: Option<&[usize]>, values: &mut dyn Iterator<Item = &A>| {
+        line.clear();
+        if let Some(index) = index {
+            for (axis, i) in index.iter().enumerate() {
+                if axis > 0 {
+                    line.push(INDEX_SEPARATOR);
+                }
+                write!(line, "{}", i).unwrap();
+            }
+            line.push(options.delimiter);
+        }
+        for (column, value) in values.enumerate() {
+            if column > 0 {
+                line.push(options.delimiter);
+            }
+            value.format(options.precision, &mut line)?;
+        }
+        line.push('\n');
+        writer.write_all(line.as_bytes()).map_err(TextError::Io)
+    };
+    match array.ndim() {
+        0 | 1 => {
+            for value in array.iter() {
+                write_line(None, &mut std::iter::once(value))?;
+            }
+        }
+        2 => {
+            for row in array.outer_iter() {
+                write_line(None, &mut row.iter())?;
+            }
+        }
+        ndim => {
+            let last = ndim - 1;
+            let outer_shape = &array.shape()[..last];
+            for (row, values) in array.lanes(Axis(last)).into_iter().enumerate() {
+                let index = unravel(row, outer_shape);
+                write_line(Some(&index), &mut values.iter())?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The multi-dimensional index of the `flat`th element in row-major order
+fn unravel(mut flat: usize, shape: &[usize]) -> Vec<usize> {
+    let mut index = vec![0; shape.len()];
+    for (i, &len) in index.iter_mut().zip(shape).rev() {
+        *i = flat % len;
+        flat /= len;
+    }
+    index
+}
+
+/// Write an array as delimited text
+pub fn write_text<'a, W: Write>(writer: &mut W, array: impl Into<SaneView<'a>>, options: &TextOptions) -> Result<(), TextError> {
+    let array = array.into();
+    check_delimiter(options)?;
+    if !supports_text(array.data_type()) {
+        return Err(TextError::UnsupportedDataType(array.data_type()));
+    }
+    crate::sane_dispatch!(SaneView: array, array => write_lines(writer, array, options))
+}
+
+/// The fields of each non-empty line, with the line numbers counted from 1
+fn read_lines<R: BufRead>(reader: R, delimiter: char) -> Result<Vec<(usize, Vec<String>)>, TextError> {
+    let mut lines = vec![];
+    for (number, line) in reader.lines().enumerate() {
+        let line = line.map_err(TextError::Io)?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let fields = line.split(delimiter).map(|field| field.trim().to_string()).collect();
+        lines.push((number + 1, fields));
+    }
+    Ok(lines)
+}
+
+fn parse_index(text: &str) -> Option<Vec<usize>> {
+    text.split(INDEX_SEPARATOR).map(|i| i.parse().ok()).collect()
+}
+
+/// The shape of the array described by `lines`, checking that its index column (if any) counts
+/// through the indices in row-major order
+fn text_shape(lines: &[(usize, Vec<String>)]) -> Result<(Vec<usize>, bool), TextError> {
+    let Some((_, first)) = lines.first() else {
+        return Ok((vec![0], false));
+    };
+    for (number, fields) in lines {
+        if fields.len() != first.len() {
+            return Err(TextError::RaggedLine(*number));
+        }
+    }
+    let indexed = first.len() > 1 && first[0].contains(INDEX_SEPARATOR);
+    if !indexed {
+        return Ok(match first.len() {
+            1 => (vec![lines.len()], false),
+            columns => (vec![lines.len(), columns], false),
+        });
+    }
+    let indices = lines.iter()
+        .map(|(number, fields)| parse_index(&fields[0]).ok_or(TextError::InvalidIndex(*number)))
+        .collect::<Result<Vec<_>, _>>()?;
+    let (last_number, last_index) = (lines[lines.len() - 1].0, &indices[indices.len() - 1]);
+    let outer_shape = last_index.iter()
+        .map(|i| i.checked_add(1))
+        .collect::<Option<Vec<usize>>>()
+        .ok_or(TextError::InvalidIndex(last_number))?;
+    let outer_len = outer_shape.iter().try_fold(1usize, |len, &dim| len.checked_mul(dim));
+    if outer_len != Some(lines.len()) {
+        return Err(TextError::InvalidIndex(last_number));
+    }
+    for (row, ((number, _), index)) in lines.iter().zip(&indices).enumerate() {
+        if *index != unravel(row, &outer_shape) {
+            return Err(TextError::InvalidIndex(*number));
+        }
+    }
+    let mut shape = outer_shape;
+    shape.push(first.len() - 1);
+    Ok((shape, true))
+}
+
+fn parse_values<A: TextElement>(lines: &[(usize, Vec<String>)], indexed: bool) -> Result<Vec<A>, TextError> {
+    let skip = usize::from(indexed);
+    let mut values = vec![];
+    for (number, fields) in lines {
+        for (column, field) in fields.iter().enumerate().skip(skip) {
+            let value = A::parse(field).ok_or_else(|| TextError::InvalidValue(*number, column + 1, field.clone()))?;
+            values.push(value);
+        }
+    }
+    Ok(values)
+}
+
+/// Parse delimited text as written by [`write_text`] into an array with the given data type
+pub fn read_text<R: BufRead>(reader: R, data_type: DataType, options: &TextOptions) -> Result<Sane, TextError> {
+    check_delimiter(options)?;
+    if !supports_text(data_type) {
+        return Err(TextError::UnsupportedDataType(data_type));
+    }
+    let lines = read_lines(reader, options.delimiter)?;
+    let (shape, indexed) = text_shape(&lines)?;
+    crate::sane_dispatch!(
+        DataType: data_type,
+        A => {
+            let values = parse_values::<A>(&lines, indexed)?;
+            let array = ArrayD::from_shape_vec(IxDyn(&shape), values).map_err(TextError::ShapeError)?;
+            Ok(Sane::from(array))
+        },
+        _ => Err(TextError::UnsupportedDataType(data_type))
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use ndarray::{array, Array3};
+
+    use super::{read_text, write_text, TextError, TextOptions};
+    use crate::{DataType, Sane};
+
+    fn to_text(sane: &Sane, options: &TextOptions) -> String {
+        let mut text = vec![];
+        write_text(&mut text, sane, options).unwrap();
+        String::from_utf8(text).unwrap()
+    }
+
+    #[test]
+    fn rows_and_columns() {
+        let matrix = Sane::from(array![[1.5f64, -2.0], [0.25, 4.0]]);
+        let text = to_text(&matrix, &TextOptions::CSV);
+        assert_eq!(text, "1.5,-2\n0.25,4\n");
+        assert_eq!(read_text(text.as_bytes(), DataType::F64, &TextOptions::CSV).unwrap(), matrix);
+
+        let options = TextOptions { precision: Some(2), ..TextOptions::TSV };
+        assert_eq!(to_text(&matrix, &options), "1.50\t-2.00\n0.25\t4.00\n");
+
+        let column = Sane::from(array![true, false, true]);
+        let text = to_text(&column, &TextOptions::CSV);
+        assert_eq!(text, "true\nfalse\ntrue\n");
+        assert_eq!(read_text(text.as_bytes(), DataType::Bool, &TextOptions::CSV).unwrap(), column);
+    }
+
+    #[test]
+    fn index_column() {
+        let array = Array3::from_shape_fn((2, 2, 3), |(i, j, k)| (i * 100 + j * 10 + k) as u16);
+        let sane = Sane::from(array);
+        let text = to_text(&sane, &TextOptions::CSV);
+        assert_eq!(text.lines().next(), Some("0:0,0,1,2"));
+        assert_eq!(text.lines().nth(2), Some("1:0,100,101,102"));
+        assert_eq!(read_text(text.as_bytes(), DataType::U16, &TextOptions::CSV).unwrap(), sane);
+
+        let shuffled = "0:0,1\n1:0,3\n0:1,2\n1:1,4\n";
+        assert!(matches!(read_text(shuffled.as_bytes(), DataType::U8, &TextOptions::CSV), Err(TextError::InvalidIndex(2))));
+        let overflow = "4294967296:4294967296,1\n";
+        assert!(matches!(read_text(overflow.as_bytes(), DataType::F32, &TextOptions::CSV), Err(TextError::InvalidIndex(1))));
+        let max_index = format!("{}:0,1\n", usize::MAX);
+        assert!(matches!(read_text(max_index.as_bytes(), DataType::F32, &TextOptions::CSV), Err(TextError::InvalidIndex(1))));
+    }
+
+    #[test]
+    fn invalid_text() {
+        let ragged = "1,2\n3\n";
+        assert!(matches!(read_text(ragged.as_bytes(), DataType::I32, &TextOptions::CSV), Err(TextError::RaggedLine(2))));
+        let invalid = "1,2\n3,x\n";
+        let err = read_text(invalid.as_bytes(), DataType::I32, &TextOptions::CSV).unwrap_err();
+        assert!(matches!(err, TextError::InvalidValue(2, 2, value) if value == "x"));
+        let overflow = "256\n";
+        assert!(matches!(read_text(overflow.as_bytes(), DataType::U8, &TextOptions::CSV), Err(TextError::InvalidValue(1, 1, _))));
+    }
+
+    #[test]
+    fn invalid_delimiter() {
+        let sane = Sane::from(Array3::<u8>::zeros((2, 1, 2)));
+        for delimiter in [':', '-', '.', '1', 'e'] {
+            let options = TextOptions { delimiter, ..TextOptions::CSV };
+            let err = write_text(&mut vec![], &sane, &options).unwrap_err();
+            assert!(matches!(err, TextError::InvalidDelimiter(d) if d == delimiter));
+            let err = read_text("0:0:0:0\n".as_bytes(), DataType::U8, &options).unwrap_err();
+            assert!(matches!(err, TextError::InvalidDelimiter(d) if d == delimiter));
+        }
+        let options = TextOptions { delimiter: ';', ..TextOptions::CSV };
+        assert_eq!(read_text(to_text(&sane, &options).as_bytes(), DataType::U8, &options).unwrap(), sane);
+    }
+}
@@ -0,0 +1,167 @@
+use ndarray::{ArrayD, ArrayViewD};
+
+use crate::data::{Sane, SaneView};
+use crate::element::SaneElement;
+
+/// An operation that is generic over the element type, to be run on whichever array a [`Sane`]
+/// holds using [`Sane::visit`]
+pub trait SaneVisitor {
+    type Output;
+
+    fn visit<A: SaneElement>(self, array: ArrayD<A>) -> Self::Output;
+}
+
+/// An operation that is generic over the element type, to be run on whichever array a
+/// [`SaneView`] borrows using [`SaneView::visit`]
+pub trait SaneViewVisitor<'a> {
+    type Output;
+
+    fn visit<A: SaneElement>(self, array: ArrayViewD<'a, A>) -> Self::Output;
+}
+
+impl Sane {
+    /// Run `visitor` on the array, whatever the type of its elements
+    pub fn visit<V: SaneVisitor>(self, visitor: V) -> V::Output {
+        crate::sane_dispatch!(Sane: self, array => visitor.visit(array))
+    }
+}
+
+impl<'a> SaneView<'a> {
+    /// Run `visitor` on the viewed array, whatever the type of its elements
+    pub fn visit<V: SaneViewVisitor<'a>>(self, visitor: V) -> V::Output {
+        crate::sane_dispatch!(SaneView: self, array => visitor.visit(array))
+    }
+}
+
+/// Evaluate an expression for whichever element type a value holds.
+///
+/// With `Sane`, `SaneView` or `SaneViewMut`, the pattern is matched against the array held by
+/// the variant of the given value (which can also be a reference), and the expression is
+/// evaluated with the element type of that variant:
+///
+/// ```
+/// use sane_array::{sane_dispatch, Sane};
+///
+/// let sane = Sane::from(ndarray::array![1u8, 2, 3]);
+/// let sum: f64 = sane_dispatch!(Sane: &sane, array => array.len() as f64);
+/// assert_eq!(sum, 3.0);
+/// ```
+///
+/// With `DataType`, the given name is bound as an alias of the corresponding element type. Data
+/// types whose element types are not enabled in this build evaluate the `_` expression instead:
+///
+/// ```
+/// use sane_array::{sane_dispatch, DataType, SaneElement};
+///
+/// let data_type = DataType::I16;
+/// let size = sane_dispatch!(DataType: data_type, A => A::SIZE, _ => 0);
+/// assert_eq!(size, 2);
+/// ```
+///
+/// The list of element types lives in this macro only, so adding an element type extends every
+/// use of it.
+#[macro_export]
+macro_rules! sane_dispatch {
+    (DataType: $data_type:expr, $element:ident => $body:expr, _ => $fallback:expr) => {
+        $crate::__sane_element_types!(__sane_dispatch_data_type ($data_type, $element, $body, $fallback))
+    };
+    ($enum:ident: $value:expr, $array:pat => $body:expr) => {
+        $crate::__sane_element_types!(__sane_dispatch_variant ($enum, $value, $array, $body))
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __sane_dispatch_variant {
+    (($enum:ident, $value:expr, $array:pat, $body:expr) [$(($data_type:ident, $variant:ident, $element:ty))*]) => {
+        match $value {
+            $($crate::$enum::$variant($array) => $body,)*
+            // The enums are non-exhaustive outside this crate, even though every variant is listed
+            #[allow(unreachable_patterns)]
+            _ => unreachable!(),
+        }
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __sane_dispatch_data_type {
+    (($value:expr, $alias:ident, $body:expr, $fallback:expr) [$(($data_type:ident, $variant:ident, $element:ty))*]) => {
+        match $value {
+            $($crate::DataType::$data_type => {
+                #[allow(dead_code)]
+                type $alias = $element;
+                $body
+            })*
+            #[allow(unreachable_patterns)]
+            _ => $fallback,
+        }
+    };
+}
+
+// The element types are collected as `(DataType, variant, element type)` triples, passing the
+// list through one macro per optional feature before handing it to `$callback`.
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __sane_element_types {
+    ($callback:ident $args:tt) => {
+        $crate::__sane_element_types_half!($callback $args [
+            (F32, ArrayF32, f32)
+            (I32, ArrayI32, i32)
+            (U32, ArrayU32, u32)
+            (F64, ArrayF64, f64)
+            (I64, ArrayI64, i64)
+            (U64, ArrayU64, u64)
+            (I8, ArrayI8, i8)
+            (U8, ArrayU8, u8)
+            (I16, ArrayI16, i16)
+            (U16, ArrayU16, u16)
+            (Bool, ArrayBool, bool)
+        ])
+    };
+}
+
+#[cfg(feature = "half")]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __sane_element_types_half {
+    ($callback:ident $args:tt [$($types:tt)*]) => {
+        $crate::__sane_element_types_complex!($callback $args [
+            $($types)*
+            (F16, ArrayF16, $crate::half::f16)
+            (BF16, ArrayBF16, $crate::half::bf16)
+        ])
+    };
+}
+
+#[cfg(not(feature = "half"))]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __sane_element_types_half {
+    ($callback:ident $args:tt [$($types:tt)*]) => {
+        $crate::__sane_element_types_complex!($callback $args [$($types)*])
+    };
+}
+
+#[cfg(feature = "num-complex")]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __sane_element_types_complex {
+    ($callback:ident $args:tt [$($types:tt)*]) => {
+        $crate::$callback!($args [
+            $($types)*
+            (C64, ArrayC64, $crate::num_complex::Complex<f32>)
+            (C128, ArrayC128, $crate::num_complex::Complex<f64>)
+        ])
+    };
+}
+
+#[cfg(not(feature = "num-complex"))]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __sane_element_types_complex {
+    ($callback:ident $args:tt [$($types:tt)*]) => {
+        $crate::$callback!($args [$($types)*])
+    };
+}
@@ -1,65 +1,13 @@
+use std::borrow::Cow;
 use std::io::prelude::Write;
-use std::mem::size_of;
-use std::slice::from_raw_parts;
+use std::io::{ErrorKind, IoSlice};
 use std::error::Error;
 
-use ndarray::{Dimension, ArrayBase, Data};
+use ndarray::{Dimension, ArrayBase, ArrayViewD, Data};
 
-use crate::{data::{SaneData, data_type_code}, Sane};
-
-/// To be able to write SANE data we need to be able to
-/// convert an element to a byte sequence
-pub trait WriteSane: SaneData {
-    fn to_le_bytes(elem: Self) -> Vec<u8>;
-}
-
-impl WriteSane for f32 {
-    fn to_le_bytes(elem: f32) -> Vec<u8> {
-        f32::to_le_bytes(elem).to_vec()
-    }
-}
-
-impl WriteSane for i32 {
-    fn to_le_bytes(elem: i32) -> Vec<u8> {
-        i32::to_le_bytes(elem).to_vec()
-    }
-}
-
-impl WriteSane for u32 {
-    fn to_le_bytes(elem: u32) -> Vec<u8> {
-        u32::to_le_bytes(elem).to_vec()
-    }
-}
-
-impl WriteSane for f64 {
-    fn to_le_bytes(elem: f64) -> Vec<u8> {
-        f64::to_le_bytes(elem).to_vec()
-    }
-}
-
-impl WriteSane for i64 {
-    fn to_le_bytes(elem: i64) -> Vec<u8> {
-        i64::to_le_bytes(elem).to_vec()
-    }
-}
-
-impl WriteSane for u64 {
-    fn to_le_bytes(elem: u64) -> Vec<u8> {
-        u64::to_le_bytes(elem).to_vec()
-    }
-}
-
-impl WriteSane for i8 {
-    fn to_le_bytes(elem: i8) -> Vec<u8> {
-        i8::to_le_bytes(elem).to_vec()
-    }
-}
-
-impl WriteSane for u8 {
-    fn to_le_bytes(elem: u8) -> Vec<u8> {
-        vec![elem]
-    }
-}
+use crate::data::{SaneView, data_type_code};
+use crate::element::SaneElement;
+use crate::visit::SaneViewVisitor;
 
 #[derive(Debug)]
 pub enum WriteError {
@@ -87,70 +35,121 @@ impl Error for WriteError {
     }
 }
 
-fn write_header<F: Write, A: SaneData, D: Dimension, Repr>(file: &mut F, array: &ArrayBase<Repr, D>)  -> Result<(), WriteError>
+/// Arrays with at most this many dimensions have their header encoded on the stack
+const STACK_HEADER_DIMS: usize = 16;
+
+/// Length in bytes of the SANE header of an array with `ndim` dimensions
+pub const fn header_len(ndim: usize) -> usize {
+    // magic + shape length + shape + data type + data length
+    4 + 4 + 8 * ndim + 1 + 8
+}
+
+/// Length in bytes of the SANE encoding of an array, including its header.
+///
+/// This can be used to pre-size buffers, e.g. a `Vec<u8>` that arrays are written to.
+pub fn encoded_len<A: SaneElement, D: Dimension, Repr>(array: &ArrayBase<Repr, D>) -> usize
 where
     Repr: Data<Elem = A>
 {
-    let shape = array.shape();
-    let data_type = A::sane_data_type();
-    let magic = "SANE".as_bytes();
-    file.write_all(magic).map_err(WriteError::Failed)?;
+    header_len(array.ndim()) + array.len() * A::SIZE
+}
+
+fn encode_header<A: SaneElement>(shape: &[usize], len: usize, buf: &mut [u8]) -> Result<(), WriteError> {
     let shape_length = u32::try_from(shape.len()).map_err(WriteError::ShapeTooLong)?;
-    let shape_length_bytes = shape_length.to_le_bytes();
-    file.write_all(&shape_length_bytes).map_err(WriteError::Failed)?;
+    buf[0..4].copy_from_slice("SANE".as_bytes());
+    buf[4..8].copy_from_slice(&shape_length.to_le_bytes());
+    let mut offset = 8;
     for &dim in shape.iter().rev() {
         let dimension = u64::try_from(dim).map_err(WriteError::DimTooLarge)?;
-        let dim_bytes = dimension.to_le_bytes();
-        file.write_all(&dim_bytes).map_err(WriteError::Failed)?
+        buf[offset..offset + 8].copy_from_slice(&dimension.to_le_bytes());
+        offset += 8;
     }
-    let code = data_type_code(data_type);
-    file.write_all(&[code]).map_err(WriteError::Failed)?;
-    let byte_length = array.len() * size_of::<A>();
+    buf[offset] = data_type_code(A::DATA_TYPE);
+    let byte_length = len * A::SIZE;
     let data_length = u64::try_from(byte_length).map_err(WriteError::TooMuchData)?;
-    let data_length_bytes = data_length.to_le_bytes();
-    file.write_all(&data_length_bytes).map_err(WriteError::Failed)?;
+    buf[offset + 1..offset + 9].copy_from_slice(&data_length.to_le_bytes());
     Ok(())
 }
 
-fn write_data<F: Write, A: WriteSane, D: Dimension, Repr>(file: &mut F, array: &ArrayBase<Repr, D>) -> Result<(), WriteError>
+/// The little-endian bytes of the array elements in row-major order, borrowed from the array
+/// whenever its memory layout already matches
+pub(crate) fn data_bytes<A: SaneElement, D: Dimension, Repr>(array: &ArrayBase<Repr, D>) -> Cow<'_, [u8]>
 where
     Repr: Data<Elem = A>
 {
-    let data_ptr = array.as_ptr();
-    let byte_length = array.len() * size_of::<A>();
-    if cfg!(endianness = "little") {
-        let data_ptr_bytes = data_ptr.cast::<u8>();
-        let data_bytes = unsafe { from_raw_parts(data_ptr_bytes, byte_length) };
-        file.write_all(data_bytes).map_err(WriteError::Failed)?;
-    } else {
-        for &elem in array.iter() {
-            let elem_bytes = WriteSane::to_le_bytes(elem);
-            file.write_all(&elem_bytes).map_err(WriteError::Failed)?;
+    match array.as_slice() {
+        Some(values) if cfg!(target_endian = "little") => Cow::Borrowed(bytemuck::cast_slice(values)),
+        Some(values) => {
+            let mut bytes = vec![0; values.len() * A::SIZE];
+            A::encode_le(values, &mut bytes);
+            Cow::Owned(bytes)
+        }
+        None => {
+            let values: Vec<A> = array.iter().copied().collect();
+            let mut bytes = vec![0; values.len() * A::SIZE];
+            A::encode_le(&values, &mut bytes);
+            Cow::Owned(bytes)
+        }
+    }
+}
+
+/// Like [`Write::write_all`] but for a sequence of buffers
+fn write_all_vectored<F: Write, const N: usize>(file: &mut F, mut bufs: [&[u8]; N]) -> std::io::Result<()> {
+    let mut first = 0;
+    while first < N {
+        if bufs[first].is_empty() {
+            first += 1;
+            continue;
+        }
+        let slices = bufs.map(IoSlice::new);
+        match file.write_vectored(&slices[first..]) {
+            Ok(0) => return Err(std::io::Error::new(ErrorKind::WriteZero, "failed to write whole buffer")),
+            Ok(mut n) => {
+                while n > 0 {
+                    let advance = n.min(bufs[first].len());
+                    bufs[first] = &bufs[first][advance..];
+                    n -= advance;
+                    if bufs[first].is_empty() {
+                        first += 1;
+                    }
+                }
+            }
+            Err(e) if e.kind() == ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
         }
     }
     Ok(())
 }
 
 /// Write array into a SANE-encoded file
-pub fn write_sane<F: Write, A: WriteSane, D: Dimension, Repr>(file: &mut F, array: &ArrayBase<Repr, D>) -> Result<(), WriteError>
+pub fn write_sane<F: Write, A: SaneElement, D: Dimension, Repr>(file: &mut F, array: &ArrayBase<Repr, D>) -> Result<(), WriteError>
 where
     Repr: Data<Elem = A>
 {
-    write_header(file, &array)?;
-    write_data(file, &array)?;
-    Ok(())
+    let shape = array.shape();
+    let mut stack_header = [0u8; header_len(STACK_HEADER_DIMS)];
+    let mut heap_header = vec![];
+    let header = if shape.len() <= STACK_HEADER_DIMS {
+        &mut stack_header[..header_len(shape.len())]
+    } else {
+        heap_header.resize(header_len(shape.len()), 0);
+        &mut heap_header[..]
+    };
+    encode_header::<A>(shape, array.len(), header)?;
+    let data = data_bytes(array);
+    write_all_vectored(file, [header, &data]).map_err(WriteError::Failed)
 }
 
 /// Write array into SANE-encoded file, returning [`std::io::Error`]s
-pub fn write_sane_io<F: Write, A: WriteSane, D: Dimension, Repr>(file: &mut F, array: &ArrayBase<Repr, D>) -> Result<(), std::io::Error>
+pub fn write_sane_io<F: Write, A: SaneElement, D: Dimension, Repr>(file: &mut F, array: &ArrayBase<Repr, D>) -> Result<(), std::io::Error>
 where
     Repr: Data<Elem = A>
 {
-    write_sane(file, array).map_err(|err| std::io::Error::new(std::io::ErrorKind::Other, err))
+    write_sane(file, array).map_err(|err| std::io::Error::new(ErrorKind::Other, err))
 }
 
 /// Write multiple SANE-encoded arrays to a file
-pub fn write_sane_arrays<'a, F: Write, A: WriteSane + 'a, D: Dimension + 'a, Arrays, Repr>(
+pub fn write_sane_arrays<'a, F: Write, A: SaneElement + 'a, D: Dimension + 'a, Arrays, Repr>(
     mut file: F,
     arrays: Arrays,
 ) -> Result<(), WriteError>
@@ -165,7 +164,7 @@ where
 }
 
 /// Write multiple SANE-encoded arrays to a file, returning [`std::io::Error`]s
-pub fn write_sane_arrays_io<'a, F: Write, A: WriteSane + 'a, D: Dimension + 'a, Arrays, Repr>(
+pub fn write_sane_arrays_io<'a, F: Write, A: SaneElement + 'a, D: Dimension + 'a, Arrays, Repr>(
     mut file: F,
     arrays: Arrays,
 ) -> Result<(), std::io::Error>
@@ -180,26 +179,72 @@ where
 }
 
 
-/// Write multiple SANE-encoded arrays to a file, each with a dynamic shape and data type
+/// Writes the visited array to `file`
+struct WriteVisitor<'f, F> {
+    file: &'f mut F,
+}
+
+impl<'a, 'f, F: Write> SaneViewVisitor<'a> for WriteVisitor<'f, F> {
+    type Output = Result<(), WriteError>;
+
+    fn visit<A: SaneElement>(self, array: ArrayViewD<'a, A>) -> Self::Output {
+        write_sane(self.file, &array)
+    }
+}
+
+/// Write a single SANE-encoded array with dynamic shape and data type
+pub fn write_sane_dyn<'a, F: Write, S: Into<SaneView<'a>>>(file: &mut F, sane: S) -> Result<(), WriteError> {
+    sane.into().visit(WriteVisitor { file })
+}
+
+/// Write multiple SANE-encoded arrays to a file, each with a dynamic shape and data type.
+///
+/// The arrays can be given as `&Sane` or as borrowed [`SaneView`]s, so arrays of different
+/// types can be written without copying them into [`Sane`] values first.
 pub fn write_sane_arrays_dyn<'a, F: Write, Arrays>(
     mut file: F,
     arrays: Arrays,
 ) -> Result<(), WriteError>
 where
-    Arrays: IntoIterator<Item = &'a Sane>
+    Arrays: IntoIterator,
+    Arrays::Item: Into<SaneView<'a>>
 {
-    use Sane::*;
     for sane in arrays.into_iter() {
-        match sane {
-           ArrayF32(array) => write_sane(&mut file, array)?,
-           ArrayI32(array) => write_sane(&mut file, array)?,
-           ArrayU32(array) => write_sane(&mut file, array)?,
-           ArrayF64(array) => write_sane(&mut file, array)?,
-           ArrayI64(array) => write_sane(&mut file, array)?,
-           ArrayU64(array) => write_sane(&mut file, array)?,
-           ArrayI8(array) => write_sane(&mut file, array)?,
-           ArrayU8(array) => write_sane(&mut file, array)?,
-        }
+        write_sane_dyn(&mut file, sane)?;
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use ndarray::array;
+
+    use super::write_sane;
+
+    /// Accepts at most three bytes per call, like a slow pipe
+    struct Trickle(Vec<u8>);
+
+    impl Write for Trickle {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            let n = buf.len().min(3);
+            self.0.extend_from_slice(&buf[..n]);
+            Ok(n)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn short_writes() {
+        let array = array![[1u32, 2, 3], [4, 5, 6]];
+        let mut expected = vec![];
+        write_sane(&mut expected, &array).unwrap();
+        let mut trickle = Trickle(vec![]);
+        write_sane(&mut trickle, &array).unwrap();
+        assert_eq!(trickle.0, expected);
+    }
+}